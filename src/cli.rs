@@ -0,0 +1,64 @@
+use serde::Serialize;
+
+use crate::{
+	contracts::compiler,
+	error::Error::InvalidParam,
+	ethereum::{
+		self,
+		contract::{deploy_sol_contract, DeployContractRequest},
+		network,
+	},
+	Result,
+};
+
+const USAGE: &str = "usage:
+  web3-examples compile <source>
+  web3-examples deploy <source> <contract> (--from <account> | --signer <signer>)
+                [--network <name>] [--alias <alias>] [--params <json>] [--confirmations <n>]";
+
+/// Run a command instead of the server, the result is printed as json.
+pub(crate) async fn run(args: &[String]) -> Result<()> {
+	match args {
+		[cmd, source] if cmd == "compile" => print(&compiler::compile(source).await?),
+		[cmd, source, contract, options @ ..] if cmd == "deploy" => {
+			let mut request = DeployContractRequest {
+				contract_name: contract.clone(),
+				source: Some(source.clone()),
+				..Default::default()
+			};
+			let mut network_name = None;
+			for option in options.chunks(2) {
+				match option {
+					[key, value] if key == "--from" => request.from_account = Some(value.clone()),
+					[key, value] if key == "--signer" => request.signer = Some(value.clone()),
+					[key, value] if key == "--network" => network_name = Some(value.as_str()),
+					[key, value] if key == "--alias" => request.alias = Some(value.clone()),
+					[key, value] if key == "--params" => {
+						request.contract_params = serde_json::from_str(value)
+							.map_err(|e| InvalidParam(format!("params: {}", e)))?
+					}
+					[key, value] if key == "--confirmations" => {
+						request.confirmations = value
+							.parse()
+							.map_err(|_| InvalidParam(format!("{}\n{}", value, USAGE)))?
+					}
+					_ => return Err(InvalidParam(format!("{:?}\n{}", option, USAGE))),
+				}
+			}
+			if request.from_account.is_none() && request.signer.is_none() {
+				return Err(InvalidParam(format!("--from or --signer is required\n{}", USAGE)));
+			}
+
+			ethereum::init().await?;
+			let network = network(network_name)?;
+			let submitted = deploy_sol_contract(network, request).await?;
+			print(&network.tracker.wait(network, submitted.hash).await?)
+		}
+		_ => Err(InvalidParam(format!("unknown command\n{}", USAGE))),
+	}
+}
+
+fn print<T: Serialize>(result: &T) -> Result<()> {
+	println!("{}", serde_json::to_string_pretty(result).unwrap_or_default());
+	Ok(())
+}
@@ -0,0 +1,357 @@
+use std::{
+	collections::BTreeMap,
+	fs,
+	net::SocketAddr,
+	path::{Path, PathBuf},
+	time::Duration,
+};
+
+use log::info;
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+use web3::types::H160;
+
+use crate::{error::Error::ConfigError, routes::ETH_ROUTE_SEGMENTS, Result};
+
+const CONFIG_PATH_ENV: &str = "WEB3_CONFIG";
+const DEFAULT_CONFIG_PATH: &str = "./config.toml";
+
+static CONFIG: OnceCell<Config> = OnceCell::new();
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct Config {
+	/// Network of requests which don't name one.
+	pub default_network: String,
+	pub server: ServerConfig,
+	pub contracts: ContractsConfig,
+	pub signers: SignersConfig,
+	pub wallet: WalletConfig,
+	pub networks: BTreeMap<String, NetworkConfig>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ServerConfig {
+	pub bind_addr: SocketAddr,
+	pub request_timeout_secs: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct ContractsConfig {
+	/// Directory of the contract artifacts, subdirectories included.
+	pub artifacts_dir: PathBuf,
+	/// Directory the artifacts uploaded through the api are kept in.
+	pub store_dir: PathBuf,
+	/// Directory of the solidity sources which can be compiled through the api.
+	pub sources_dir: PathBuf,
+	/// The solc binary, a name on the `PATH` or a path.
+	pub solc_path: String,
+	/// Sqlite file recording the deployed contract instances.
+	pub deployments_db: PathBuf,
+	/// Interval of checking the artifacts for changes, 0 disables the hot reload.
+	pub reload_secs: u64,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct SignersConfig {
+	/// Directory of the v3 keystore files the signers are unlocked from.
+	pub keystore_dir: PathBuf,
+	/// File holding the keystore passphrase, `WEB3_KEYSTORE_PASSPHRASE` takes precedence.
+	pub passphrase_file: Option<PathBuf>,
+	/// JSON-RPC url of a remote signer like Web3Signer or Clef.
+	pub remote_url: Option<String>,
+	/// Accounts of the remote signer, asked by `eth_accounts` when left out.
+	pub remote_accounts: Vec<H160>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct WalletConfig {
+	/// File holding the BIP-39 mnemonic of the wallet, `WEB3_MNEMONIC` takes precedence.
+	pub mnemonic_file: Option<PathBuf>,
+	/// Accounts derived at startup.
+	pub accounts: u32,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+pub struct NetworkConfig {
+	pub rpc_url: String,
+	/// More endpoints of the same chain, together with `rpc_url` they form a failover pool.
+	pub upstreams: Vec<String>,
+	/// Expected chain id of the node, startup fails if the node reports another one.
+	pub chain_id: Option<u64>,
+	pub rpc_timeout_secs: u64,
+	/// Connect attempts of ws/ipc endpoints before a request fails.
+	pub reconnect_attempts: u32,
+	/// Delay before the first reconnect, doubled on each further attempt.
+	pub reconnect_backoff_ms: u64,
+	pub health_check_secs: u64,
+	/// Blocks an upstream may fall behind the highest one before it is taken out of the pool.
+	pub max_block_lag: u64,
+	/// Share of failed requests between two health checks that takes an upstream out.
+	pub max_error_rate: f64,
+	/// Factor applied to `eth_estimateGas` results, as a margin for state changes.
+	pub gas_multiplier: f64,
+}
+
+impl Default for Config {
+	fn default() -> Self {
+		Config {
+			default_network: "local".to_string(),
+			server: ServerConfig::default(),
+			contracts: ContractsConfig::default(),
+			signers: SignersConfig::default(),
+			wallet: WalletConfig::default(),
+			networks: BTreeMap::from([("local".to_string(), NetworkConfig::default())]),
+		}
+	}
+}
+
+impl Default for ServerConfig {
+	fn default() -> Self {
+		let addr = if cfg!(debug_assertions) { "127.0.0.1:8080" } else { "0.0.0.0:8080" };
+		ServerConfig { bind_addr: addr.parse().unwrap(), request_timeout_secs: 60 }
+	}
+}
+
+impl Default for ContractsConfig {
+	fn default() -> Self {
+		ContractsConfig {
+			artifacts_dir: PathBuf::from("./src/contracts"),
+			store_dir: PathBuf::from("./data/contracts"),
+			sources_dir: PathBuf::from("./solidity"),
+			solc_path: "solc".to_string(),
+			deployments_db: PathBuf::from("./data/deployments.db"),
+			reload_secs: 5,
+		}
+	}
+}
+
+impl Default for SignersConfig {
+	fn default() -> Self {
+		SignersConfig {
+			keystore_dir: PathBuf::from("./data/keystore"),
+			passphrase_file: None,
+			remote_url: None,
+			remote_accounts: vec![],
+		}
+	}
+}
+
+impl Default for WalletConfig {
+	fn default() -> Self {
+		WalletConfig { mnemonic_file: None, accounts: 10 }
+	}
+}
+
+impl Default for NetworkConfig {
+	fn default() -> Self {
+		NetworkConfig {
+			rpc_url: "http://localhost:8545".to_string(),
+			upstreams: vec![],
+			chain_id: None,
+			rpc_timeout_secs: 30,
+			reconnect_attempts: 5,
+			reconnect_backoff_ms: 500,
+			health_check_secs: 15,
+			max_block_lag: 5,
+			max_error_rate: 0.5,
+			gas_multiplier: 1.2,
+		}
+	}
+}
+
+impl ServerConfig {
+	pub fn request_timeout(&self) -> Duration {
+		Duration::from_secs(self.request_timeout_secs)
+	}
+}
+
+impl NetworkConfig {
+	pub fn rpc_timeout(&self) -> Duration {
+		Duration::from_secs(self.rpc_timeout_secs)
+	}
+
+	pub fn health_check_interval(&self) -> Duration {
+		Duration::from_secs(self.health_check_secs)
+	}
+}
+
+impl Config {
+	/// Read the config file (if present), then apply `WEB3_*` environment overrides.
+	pub fn load() -> Result<Config> {
+		let path = std::env::var(CONFIG_PATH_ENV).ok();
+		let mut config = match &path {
+			Some(path) => Self::from_file(path)?,
+			None if Path::new(DEFAULT_CONFIG_PATH).exists() => {
+				Self::from_file(DEFAULT_CONFIG_PATH)?
+			}
+			None => Config::default(),
+		};
+		config.apply_overrides(|key| std::env::var(key).ok())?;
+		config.validate()?;
+		Ok(config)
+	}
+
+	fn validate(&self) -> Result<()> {
+		if let Some((name, _)) =
+			self.networks.iter().find(|(_, network)| network.gas_multiplier < 1.0)
+		{
+			return Err(ConfigError(format!("gas_multiplier of network {} is below 1", name)));
+		}
+		if let Some(name) =
+			self.networks.keys().find(|name| ETH_ROUTE_SEGMENTS.contains(&name.as_str()))
+		{
+			return Err(ConfigError(format!(
+				"network name: {} is reserved by the eth routes",
+				name
+			)));
+		}
+		if !self.networks.contains_key(&self.default_network) {
+			return Err(ConfigError(format!(
+				"default network: {} is not configured",
+				self.default_network
+			)));
+		}
+		Ok(())
+	}
+
+	fn from_file(path: &str) -> Result<Config> {
+		info!("Loading config from {}", path);
+		let content = fs::read_to_string(path)
+			.map_err(|e| ConfigError(format!("read config file {} failed, {}", path, e)))?;
+		content.parse()
+	}
+
+	fn apply_overrides<F: Fn(&str) -> Option<String>>(&mut self, env: F) -> Result<()> {
+		if let Some(addr) = env("WEB3_BIND_ADDR") {
+			self.server.bind_addr = parse_env("WEB3_BIND_ADDR", &addr)?;
+		}
+		if let Some(secs) = env("WEB3_REQUEST_TIMEOUT_SECS") {
+			self.server.request_timeout_secs = parse_env("WEB3_REQUEST_TIMEOUT_SECS", &secs)?;
+		}
+		if let Some(dir) = env("WEB3_ARTIFACTS_DIR") {
+			self.contracts.artifacts_dir = PathBuf::from(dir);
+		}
+		if let Some(dir) = env("WEB3_CONTRACT_STORE_DIR") {
+			self.contracts.store_dir = PathBuf::from(dir);
+		}
+		if let Some(path) = env("WEB3_SOLC_PATH") {
+			self.contracts.solc_path = path;
+		}
+		if let Some(path) = env("WEB3_DEPLOYMENTS_DB") {
+			self.contracts.deployments_db = PathBuf::from(path);
+		}
+		if let Some(dir) = env("WEB3_KEYSTORE_DIR") {
+			self.signers.keystore_dir = PathBuf::from(dir);
+		}
+		if let Some(url) = env("WEB3_REMOTE_SIGNER_URL") {
+			self.signers.remote_url = Some(url);
+		}
+		if let Some(name) = env("WEB3_NETWORK") {
+			self.default_network = name;
+		}
+
+		// The node overrides apply to the default network.
+		let url = env("WEB3_RPC_URL");
+		let chain_id = env("WEB3_CHAIN_ID");
+		let timeout = env("WEB3_RPC_TIMEOUT_SECS");
+		if url.is_none() && chain_id.is_none() && timeout.is_none() {
+			return Ok(());
+		}
+
+		let network = self.networks.entry(self.default_network.clone()).or_default();
+		if let Some(url) = url {
+			network.rpc_url = url;
+		}
+		if let Some(chain_id) = chain_id {
+			network.chain_id = Some(parse_env("WEB3_CHAIN_ID", &chain_id)?);
+		}
+		if let Some(secs) = timeout {
+			network.rpc_timeout_secs = parse_env("WEB3_RPC_TIMEOUT_SECS", &secs)?;
+		}
+		Ok(())
+	}
+}
+
+impl std::str::FromStr for Config {
+	type Err = crate::error::Error;
+
+	fn from_str(s: &str) -> Result<Self, Self::Err> {
+		toml::from_str(s).map_err(|e| ConfigError(e.to_string()))
+	}
+}
+
+/// Load the config and make it globally available, must be called once before [`get`].
+pub fn init() -> Result<&'static Config> {
+	let config = Config::load()?;
+	Ok(CONFIG.get_or_init(|| config))
+}
+
+pub fn get() -> &'static Config {
+	CONFIG.get().expect("config is not initialized")
+}
+
+fn parse_env<T: std::str::FromStr>(key: &str, value: &str) -> Result<T> {
+	value.parse().map_err(|_| ConfigError(format!("env {}: {} is invalid", key, value)))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+
+	use super::Config;
+
+	#[test]
+	fn test_parse_config() {
+		let s = r#"
+			default_network = "goerli"
+
+			[server]
+			bind_addr = "0.0.0.0:9000"
+
+			[networks.goerli]
+			rpc_url = "http://node:8545"
+			chain_id = 5
+
+			[networks.mainnet]
+			rpc_url = "wss://mainnet:8546"
+		"#;
+		let config = s.parse::<Config>().unwrap();
+		assert_eq!(config.server.bind_addr.port(), 9000);
+		assert_eq!(config.server.request_timeout_secs, 60);
+		assert_eq!(config.networks.len(), 2);
+		assert_eq!(config.networks[&config.default_network].rpc_url, "http://node:8545");
+		assert_eq!(config.networks[&config.default_network].chain_id, Some(5));
+		assert_eq!(config.networks["mainnet"].chain_id, None);
+		assert!(config.validate().is_ok());
+
+		let s = r#"
+			default_network = "tx"
+
+			[networks.tx]
+			rpc_url = "http://node:8545"
+		"#;
+		assert!(s.parse::<Config>().unwrap().validate().is_err());
+	}
+
+	#[test]
+	fn test_env_overrides() {
+		let env = HashMap::from([("WEB3_RPC_URL", "ws://node:8546"), ("WEB3_CHAIN_ID", "1")]);
+		let mut config = Config::default();
+		config.apply_overrides(|key| env.get(key).map(|v| v.to_string())).unwrap();
+		assert_eq!(config.networks[&config.default_network].rpc_url, "ws://node:8546");
+		assert_eq!(config.networks[&config.default_network].chain_id, Some(1));
+
+		let env = HashMap::from([("WEB3_NETWORK", "sepolia")]);
+		config.apply_overrides(|key| env.get(key).map(|v| v.to_string())).unwrap();
+		assert!(config.validate().is_err());
+
+		let env = HashMap::from([("WEB3_CHAIN_ID", "one")]);
+		assert!(config.apply_overrides(|key| env.get(key).map(|v| v.to_string())).is_err());
+	}
+}
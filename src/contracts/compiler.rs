@@ -0,0 +1,325 @@
+use std::{
+	collections::BTreeMap,
+	fs,
+	io::Write,
+	path::{Path, PathBuf},
+	process::{Command, Stdio},
+};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value as JsonValue};
+use utoipa::ToSchema;
+use web3::signing::keccak256;
+
+use super::registry;
+use crate::{
+	config::{self, ContractsConfig},
+	error::Error::{AnyError, CompileError, ConfigError, InvalidParam, NotFound},
+	Result,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub(crate) struct CompileRequest {
+	/// File name of a source in the sources directory, like `Auction.sol`.
+	pub source: String,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct CompileResult {
+	source: String,
+	/// Names of the contracts registered from the source.
+	pub contracts: Vec<String>,
+	/// The source is unchanged since its last compilation.
+	cached: bool,
+}
+
+/// Compile a source of the sources directory with solc and register its contracts, sources
+/// which didn't change since the last compilation are not compiled again.
+pub(crate) async fn compile(source: &str) -> Result<CompileResult> {
+	let source = source.to_string();
+	let config = &config::get().contracts;
+	tokio::task::spawn_blocking(move || compile_source(config, source))
+		.await
+		.map_err(|e| AnyError(e.into()))?
+}
+
+fn compile_source(config: &ContractsConfig, source: String) -> Result<CompileResult> {
+	if !is_valid_source(&source) {
+		return Err(InvalidParam(format!("source: {} is invalid", source)));
+	}
+	let sources = read_sources(&config.sources_dir, &source)?
+		.into_iter()
+		.map(|(name, content)| (name, json!({ "content": content })))
+		.collect::<Map<_, _>>();
+
+	let input = json!({
+		"language": "Solidity",
+		"sources": sources,
+		"settings": {
+			"optimizer": { "enabled": true, "runs": 200 },
+			"outputSelection": { "*": { "*": ["abi", "evm.bytecode.object", "metadata"] } },
+		},
+	})
+	.to_string();
+	// Another solc at the same path compiles differently.
+	let version = solc_version(config)?;
+	let hash = hex::encode(keccak256(format!("{}{}", version, input).as_bytes()));
+
+	let path = compiled_path(config, &source);
+	if let Some(output) = read_output(&path).filter(|output| output["sourceHash"] == hash) {
+		return Ok(CompileResult { contracts: contract_names(&output), source, cached: true });
+	}
+
+	let mut output = run_solc(config, &input)?;
+	let messages = output["errors"].as_array().cloned().unwrap_or_default();
+	let (errors, warnings): (Vec<_>, Vec<_>) =
+		messages.iter().partition(|message| message["severity"] == "error");
+	for warning in warnings {
+		warn!("Compile {}: {}", source, message_text(warning));
+	}
+	if !errors.is_empty() {
+		let errors = errors.into_iter().map(message_text).collect::<Vec<_>>();
+		return Err(CompileError(errors.join("\n")));
+	}
+
+	output["sourceHash"] = hash.into();
+	registry::write_file(&path, &output.to_string()).map_err(|e| AnyError(e.into()))?;
+	registry::refresh();
+
+	let contracts = contract_names(&output);
+	info!("Compiled {} into {}, contracts: {:?}", source, path.display(), contracts);
+	Ok(CompileResult { source, contracts, cached: false })
+}
+
+/// Top level files of the sources directory only.
+fn is_valid_source(source: &str) -> bool {
+	source.ends_with(".sol")
+		&& source.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'))
+		&& !source.starts_with('.')
+}
+
+/// The source and what it imports from the sources directory by source unit name, imports
+/// which aren't found there are left to solc.
+fn read_sources(dir: &Path, source: &str) -> Result<BTreeMap<String, String>> {
+	let content = fs::read_to_string(dir.join(source))
+		.map_err(|_| NotFound(format!("source: {}", source)))?;
+	let mut sources = BTreeMap::from([(source.to_string(), content)]);
+	let mut pending = vec![source.to_string()];
+	while let Some(name) = pending.pop() {
+		let imports = imports(&sources[&name])
+			.into_iter()
+			.filter_map(|import| resolve_import(&name, &import))
+			.collect::<Vec<_>>();
+		for import in imports {
+			if sources.contains_key(&import) {
+				continue;
+			}
+			if let Ok(content) = fs::read_to_string(dir.join(&import)) {
+				sources.insert(import.clone(), content);
+				pending.push(import);
+			}
+		}
+	}
+	Ok(sources)
+}
+
+/// Paths of the import directives, like `import "./Lib.sol";` or
+/// `import {Lib} from "./Lib.sol";`.
+fn imports(content: &str) -> Vec<String> {
+	let mut code = content
+		.lines()
+		.map(|line| line.split("//").next().unwrap_or_default())
+		.collect::<Vec<_>>()
+		.join("\n");
+	while let Some(start) = code.find("/*") {
+		let end = code[start..].find("*/").map_or(code.len(), |end| start + end + 2);
+		code.replace_range(start..end, " ");
+	}
+
+	code.split(';')
+		.filter_map(|statement| {
+			let rest = statement.trim_start().strip_prefix("import")?;
+			if rest.starts_with(|c: char| c.is_ascii_alphanumeric() || c == '_' || c == '$') {
+				return None;
+			}
+			rest.split(['"', '\'']).nth(1).map(str::to_string)
+		})
+		.collect()
+}
+
+/// The source unit name of an import, relative ones are resolved against the importing unit.
+/// None for those which leave the sources directory.
+fn resolve_import(importer: &str, import: &str) -> Option<String> {
+	let path = if import.starts_with('/') {
+		return None;
+	} else if import.starts_with("./") || import.starts_with("../") {
+		match importer.rsplit_once('/') {
+			Some((dir, _)) => format!("{}/{}", dir, import),
+			None => import.to_string(),
+		}
+	} else {
+		import.to_string()
+	};
+	let mut parts = vec![];
+	for part in path.split('/') {
+		match part {
+			"" | "." => {}
+			".." => {
+				parts.pop()?;
+			}
+			part => parts.push(part),
+		}
+	}
+	Some(parts.join("/"))
+}
+
+fn solc_version(config: &ContractsConfig) -> Result<String> {
+	let output = Command::new(&config.solc_path)
+		.arg("--version")
+		.output()
+		.map_err(|e| ConfigError(format!("solc: {} can't be run, {}", config.solc_path, e)))?;
+	Ok(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+fn run_solc(config: &ContractsConfig, input: &str) -> Result<JsonValue> {
+	let mut child = Command::new(&config.solc_path)
+		.arg("--standard-json")
+		.arg("--base-path")
+		.arg(&config.sources_dir)
+		.stdin(Stdio::piped())
+		.stdout(Stdio::piped())
+		.stderr(Stdio::piped())
+		.spawn()
+		.map_err(|e| ConfigError(format!("solc: {} can't be run, {}", config.solc_path, e)))?;
+	child.stdin.take().unwrap().write_all(input.as_bytes()).map_err(|e| AnyError(e.into()))?;
+
+	let output = child.wait_with_output().map_err(|e| AnyError(e.into()))?;
+	serde_json::from_slice(&output.stdout).map_err(|_| {
+		CompileError(format!("solc failed, {}", String::from_utf8_lossy(&output.stderr).trim()))
+	})
+}
+
+/// Compilations are kept with the uploads so that the registry picks them up.
+fn compiled_path(config: &ContractsConfig, source: &str) -> PathBuf {
+	config.store_dir.join(registry::COMPILED_DIR).join(Path::new(source).with_extension("json"))
+}
+
+fn read_output(path: &Path) -> Option<JsonValue> {
+	serde_json::from_str(&fs::read_to_string(path).ok()?).ok()
+}
+
+fn contract_names(output: &JsonValue) -> Vec<String> {
+	let mut names = output["contracts"]
+		.as_object()
+		.into_iter()
+		.flat_map(|sources| sources.values())
+		.filter_map(JsonValue::as_object)
+		.flat_map(|contracts| contracts.keys().cloned())
+		.collect::<Vec<_>>();
+	names.sort();
+	names
+}
+
+fn message_text(message: &JsonValue) -> String {
+	message["formattedMessage"]
+		.as_str()
+		.or_else(|| message["message"].as_str())
+		.unwrap_or_default()
+		.trim()
+		.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+	use std::{fs, os::unix::fs::PermissionsExt};
+
+	use serde_json::json;
+
+	use super::{compile_source, contract_names, imports, resolve_import};
+	use crate::{
+		config::ContractsConfig,
+		error::Error::{InvalidParam, NotFound},
+	};
+
+	#[test]
+	fn test_imports() {
+		let content = r#"
+			// SPDX-License-Identifier: MIT
+			pragma solidity ^0.8.0;
+			import "./Lib.sol";
+			import {Math} from '../math/Math.sol';
+			// import "./Commented.sol";
+			/* import "./Block.sol"; */
+			import "@openzeppelin/token/ERC20.sol" as ERC20;
+			contract Storage { uint importance; }
+		"#;
+		assert_eq!(
+			imports(content),
+			["./Lib.sol", "../math/Math.sol", "@openzeppelin/token/ERC20.sol"]
+		);
+
+		assert_eq!(resolve_import("Storage.sol", "./Lib.sol").unwrap(), "Lib.sol");
+		assert_eq!(resolve_import("lib/Lib.sol", "../math/Math.sol").unwrap(), "math/Math.sol");
+		assert_eq!(resolve_import("lib/Lib.sol", "lib/Other.sol").unwrap(), "lib/Other.sol");
+		assert!(resolve_import("Storage.sol", "../Secret.sol").is_none());
+		assert!(resolve_import("Storage.sol", "/etc/Secret.sol").is_none());
+	}
+
+	#[test]
+	fn test_compile_cache() {
+		let dir = std::env::temp_dir().join(format!("compiler-{}", std::process::id()));
+		fs::create_dir_all(dir.join("sources/lib")).unwrap();
+		fs::write(dir.join("version"), "0.8.17").unwrap();
+		// Answers with the same contracts whatever the input is.
+		let output = json!({"contracts": {
+			"Storage.sol": {"Storage": {"abi": [], "evm": {"bytecode": {"object": "6080"}}}},
+			"lib/Math.sol": {"Math": {"abi": [], "evm": {"bytecode": {"object": ""}}}},
+		}});
+		let solc = dir.join("solc");
+		let script = format!(
+			"#!/bin/sh\nif [ \"$1\" = --version ]; then cat {}; exit; fi\ncat > /dev/null\necho '{}'\n",
+			dir.join("version").display(),
+			output
+		);
+		fs::write(&solc, script).unwrap();
+		fs::set_permissions(&solc, fs::Permissions::from_mode(0o755)).unwrap();
+		let config = ContractsConfig {
+			store_dir: dir.join("store"),
+			sources_dir: dir.join("sources"),
+			solc_path: solc.display().to_string(),
+			..Default::default()
+		};
+		let compile = |source: &str| compile_source(&config, source.to_string());
+
+		for source in ["../Storage.sol", "Storage", ".Storage.sol", "lib/Math.sol"] {
+			assert!(matches!(compile(source), Err(InvalidParam(_))));
+		}
+		assert!(matches!(compile("Storage.sol"), Err(NotFound(_))));
+
+		fs::write(dir.join("sources/Storage.sol"), r#"import "./lib/Math.sol";"#).unwrap();
+		fs::write(dir.join("sources/lib/Math.sol"), "library Math {}").unwrap();
+		let compiled = compile("Storage.sol").unwrap();
+		assert!(!compiled.cached);
+		assert_eq!(compiled.contracts, ["Math", "Storage"]);
+		assert!(compile("Storage.sol").unwrap().cached);
+
+		// Changes of an imported source or of the compiler compile again.
+		fs::write(dir.join("sources/lib/Math.sol"), "library Math { }").unwrap();
+		assert!(!compile("Storage.sol").unwrap().cached);
+		assert!(compile("Storage.sol").unwrap().cached);
+		fs::write(dir.join("version"), "0.8.18").unwrap();
+		assert!(!compile("Storage.sol").unwrap().cached);
+		fs::remove_dir_all(dir).unwrap();
+	}
+
+	#[test]
+	fn test_contract_names() {
+		let output = json!({"contracts": {
+			"Auction.sol": {"Auction": {}, "IAuction": {}},
+			"lib/Math.sol": {"Math": {}},
+		}});
+		assert_eq!(contract_names(&output), ["Auction", "IAuction", "Math"]);
+		assert!(contract_names(&json!({"errors": []})).is_empty());
+	}
+}
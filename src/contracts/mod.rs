@@ -1,21 +1,31 @@
 use std::{collections::HashMap, str::FromStr, vec};
 
 use serde::Deserialize;
-use serde_json::{Number, Value as JsonValue};
+use serde_json::{json, Map, Value as JsonValue};
 use web3::{
-	contract::tokens::{Tokenizable, Tokenize},
-	ethabi::Token,
-	types::{H160, U256},
+	ethabi::{decode, encode, param_type::Reader, ParamType, Token},
+	signing::keccak256,
+	types::{H160, H256, U256},
 };
 
 use crate::{error::Error, Result};
 
+pub(crate) mod compiler;
+pub(crate) mod registry;
+pub(crate) mod revert;
+
 #[derive(Debug, Deserialize)]
 pub struct ABI {
 	pub constructor: Option<ABIUnit>,
-	pub function_map: HashMap<String, ABIUnit>,
+	/// Every overload of a function name.
+	pub function_map: HashMap<String, Vec<ABIUnit>>,
+	pub errors: Vec<ABIUnit>,
+	pub events: Vec<ABIUnit>,
+	pub receive: Option<ABIUnit>,
+	pub fallback: Option<ABIUnit>,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub struct ABIUnit {
@@ -27,12 +37,16 @@ pub struct ABIUnit {
 	pub state_mutability: Option<String>,
 }
 
+#[allow(dead_code)]
 #[derive(Debug, Deserialize)]
 #[serde(rename_all(deserialize = "camelCase"))]
 pub struct Variable {
 	pub name: String,
 	pub r#type: String,
-	pub internal_type: String,
+	pub internal_type: Option<String>,
+	pub components: Option<Vec<Variable>>,
+	/// Event params which are topics instead of data.
+	pub indexed: Option<bool>,
 }
 
 #[derive(Debug, Deserialize, PartialEq, Eq, Hash, Clone, Copy)]
@@ -46,94 +60,407 @@ pub enum UnitType {
 	FALLBACK,
 }
 
-impl ABIUnit {
-	pub fn to_params(&self, json: &JsonValue) -> Result<Vec<Token>> {
-		if self.inputs.is_none() {
-			return Ok(vec![]);
-		}
-
-		let json_len = match json {
-			JsonValue::Array(arr) => arr.len(),
-			JsonValue::Object(map) => map.len(),
-			JsonValue::Null => 0,
-			_ => 1,
-		};
-		let len_matched = match &self.inputs {
-			Some(params) => params.len() == json_len,
-			None => json_len == 0,
+impl ABI {
+	/// The function called and its encoded params. Functions are named by a full signature like
+	/// `transfer(address,uint256)`, a `0x` prefixed selector or a plain name, overloads of a
+	/// plain name are told apart by the params they accept.
+	pub fn resolve(&self, function: &str, params: &JsonValue) -> Result<(&ABIUnit, Vec<Token>)> {
+		let functions = self.function_map.values().flatten();
+		let candidates: Vec<&ABIUnit> = if function.contains('(') {
+			let signature: String = function.split_whitespace().collect();
+			functions.filter(|unit| unit.signature().is_ok_and(|s| s == signature)).collect()
+		} else if let Some(selector) = function.strip_prefix("0x") {
+			let selector = hex::decode(selector).unwrap_or_default();
+			functions.filter(|unit| unit.selector().is_ok_and(|s| s == selector[..])).collect()
+		} else if let Some(units) = self.function_map.get(function) {
+			units.iter().collect()
+		} else {
+			// Plain transfers go to `receive()` or `fallback()` if the contract declares them.
+			let special = match function {
+				"receive" => self.receive.as_ref(),
+				"fallback" => self.fallback.as_ref(),
+				_ => None,
+			};
+			special.into_iter().collect()
 		};
-		if !len_matched {
-			return Err(Error::InvalidParam("params of abi parse failed".to_string()));
-		}
 
-		let mut params = Vec::with_capacity(json_len);
-		for (idx, variable) in self.inputs.as_ref().unwrap().iter().enumerate() {
-			match json {
-				JsonValue::Null => params.push(().into_tokens().into_token()),
-				JsonValue::Bool(b) => params.push(b.into_token()),
-				JsonValue::Number(num) => params.push(Self::num_to_token(num)?),
-				JsonValue::String(string) => params.push(Self::string_to_token(string, variable)?),
-				JsonValue::Array(arr) => {
-					let value = arr.get(idx).unwrap();
-					let token = Self::to_token(value, variable)?;
-					params.push(token)
+		match candidates[..] {
+			[] => Err(Error::NotFound(format!("function: {}", function))),
+			[unit] => Ok((unit, unit.to_params(params)?)),
+			_ => {
+				let mut matched = candidates
+					.iter()
+					.filter_map(|unit| unit.to_params(params).ok().map(|tokens| (*unit, tokens)));
+				match (matched.next(), matched.next()) {
+					(Some(resolved), None) => Ok(resolved),
+					(first, _) => {
+						let signatures = candidates
+							.iter()
+							.filter_map(|unit| unit.signature().ok())
+							.collect::<Vec<_>>()
+							.join(", ");
+						let reason = if first.is_some() { "is ambiguous" } else { "matches no" };
+						Err(Error::InvalidParam(format!(
+							"params of {} {} overload, use one of: {}",
+							function, reason, signatures
+						)))
+					}
 				}
-				JsonValue::Object(_) => {
-					return Err(Error::InvalidParam("Map type unsupported".to_string()));
+			}
+		}
+	}
+
+	/// Json of a log emitted by one of the events, `None` when no event matches. Indexed params
+	/// of dynamic types are only known by the hash in their topic.
+	pub fn decode_log(&self, topics: &[H256], data: &[u8]) -> Option<JsonValue> {
+		let (topic, mut indexed) =
+			topics.split_first().map(|(topic, rest)| (topic, rest.iter()))?;
+		let event = self.events.iter().find(|event| {
+			event.anonymous != Some(true)
+				&& event
+					.signature()
+					.is_ok_and(|signature| keccak256(signature.as_bytes()) == topic.0)
+		})?;
+
+		let inputs = event.inputs.as_deref().unwrap_or_default();
+		let data_types = inputs
+			.iter()
+			.filter(|input| input.indexed != Some(true))
+			.map(Variable::param_type)
+			.collect::<Result<Vec<_>>>()
+			.ok()?;
+		let mut data_tokens = decode(&data_types, data).ok()?.into_iter();
+
+		let mut params = Map::new();
+		for (idx, input) in inputs.iter().enumerate() {
+			let value = match input.indexed {
+				Some(true) => {
+					let topic = indexed.next()?;
+					match input.param_type().ok()? {
+						kind @ (ParamType::Address
+						| ParamType::Bool
+						| ParamType::Int(_)
+						| ParamType::Uint(_)
+						| ParamType::FixedBytes(_)) => input.to_json(decode(&[kind], &topic.0).ok()?.pop()?),
+						_ => JsonValue::String(format!("{:?}", topic)),
+					}
 				}
+				_ => input.to_json(data_tokens.next()?),
 			};
+			let name = if input.name.is_empty() { idx.to_string() } else { input.name.clone() };
+			params.insert(name, value);
 		}
+		Some(json!({ "event": event.signature().ok()?, "params": params }))
+	}
+}
+
+impl ABIUnit {
+	/// Canonical signature like `transfer(address,uint256)`.
+	pub fn signature(&self) -> Result<String> {
+		let types = self
+			.inputs
+			.iter()
+			.flatten()
+			.map(|input| input.param_type().map(|param_type| param_type.to_string()))
+			.collect::<Result<Vec<_>>>()?;
+		let name = match self.r#type {
+			UnitType::RECEIVE => "receive",
+			UnitType::FALLBACK => "fallback",
+			_ => self.name.as_deref().unwrap_or_default(),
+		};
+		Ok(format!("{}({})", name, types.join(",")))
+	}
+
+	pub fn selector(&self) -> Result<[u8; 4]> {
+		let hash = keccak256(self.signature()?.as_bytes());
+		Ok([hash[0], hash[1], hash[2], hash[3]])
+	}
+
+	/// Json object of the decoded return values of a function.
+	pub fn outputs_to_json(&self, tokens: Vec<Token>) -> JsonValue {
+		JsonValue::Object(tokens_to_json(self.outputs.as_deref().unwrap_or_default(), tokens))
+	}
 
-		Ok(params)
+	pub fn input_types(&self) -> Result<Vec<ParamType>> {
+		self.inputs.iter().flatten().map(Variable::param_type).collect()
 	}
 
-	fn to_token(value: &JsonValue, variable: &Variable) -> Result<Token> {
-		match value {
-			JsonValue::Bool(b) => Ok(b.into_token()),
-			JsonValue::Number(num) => Self::num_to_token(num),
-			JsonValue::String(string) => Self::string_to_token(string, variable),
-			JsonValue::Array(arr) => {
-				let mut tokens = Vec::with_capacity(arr.len());
-				for item in arr {
-					tokens.push(Self::to_token(item, variable)?);
+	pub fn output_types(&self) -> Result<Vec<ParamType>> {
+		self.outputs.iter().flatten().map(Variable::param_type).collect()
+	}
+
+	pub fn is_payable(&self) -> bool {
+		self.state_mutability.as_deref() == Some("payable")
+	}
+
+	/// Calldata of a function call, the selector followed by the encoded params.
+	pub fn encode_call(&self, params: &[Token]) -> Result<Vec<u8>> {
+		if matches!(self.r#type, UnitType::RECEIVE | UnitType::FALLBACK) {
+			return Ok(vec![]);
+		}
+		let mut data = self.selector()?.to_vec();
+		data.extend(encode(params));
+		Ok(data)
+	}
+
+	/// Tokens of the inputs given as a json array in order, as an object keyed by input name
+	/// or as a bare value for a single input.
+	pub fn to_params(&self, json: &JsonValue) -> Result<Vec<Token>> {
+		let inputs = self.inputs.as_deref().unwrap_or_default();
+		// A single array or struct input takes the json array itself when it fits, so that
+		// `["0x.."]` is one address[] argument, `[["0x.."]]` still gives it in order.
+		if let ([input], JsonValue::Array(_)) = (inputs, json) {
+			let param_type = input.param_type()?;
+			if matches!(
+				param_type,
+				ParamType::Array(_) | ParamType::FixedArray(..) | ParamType::Tuple(_)
+			) {
+				if let Ok(token) = input.to_token(json) {
+					return Ok(vec![token]);
 				}
-				Ok(tokens.into_token())
 			}
-			_ => Err(Error::InvalidParam(format!(
-				"Null and Map types are unsupported, data: {}",
-				value
-			))),
 		}
+		let values: Vec<&JsonValue> = match json {
+			JsonValue::Null => vec![],
+			JsonValue::Array(arr) if inputs.len() != 1 || arr.len() == 1 => arr.iter().collect(),
+			JsonValue::Object(map)
+				if inputs.len() > 1 || inputs.iter().all(|v| map.contains_key(&v.name)) =>
+			{
+				inputs
+					.iter()
+					.map(|variable| {
+						map.get(&variable.name).ok_or_else(|| {
+							Error::InvalidParam(format!("param: {} is missing", variable.name))
+						})
+					})
+					.collect::<Result<_>>()?
+			}
+			value => vec![value],
+		};
+		if values.len() != inputs.len() {
+			return Err(Error::InvalidParam(format!(
+				"expect {} params, got {}",
+				inputs.len(),
+				values.len()
+			)));
+		}
+
+		inputs.iter().zip(values).map(|(variable, value)| variable.to_token(value)).collect()
 	}
+}
 
-	fn num_to_token(num: &Number) -> Result<Token> {
-		let err = || Error::InvalidParam("f64 is not supported".to_string());
-		// TODO: a bug in ethabi.
-		if num.is_i64() || num.is_u64() {
-			num.as_u64().map(|n| n.into_token()).ok_or_else(err)
-		} else {
-			Err(Error::InvalidParam("f64 is not supported".to_string()))
+impl Variable {
+	/// Json of a decoded value, structs become objects keyed by their member names.
+	pub fn to_json(&self, token: Token) -> JsonValue {
+		token_to_json(token, self.components.as_deref())
+	}
+
+	/// Token of a json value, structs are given as objects keyed by member name or as arrays.
+	pub fn to_token(&self, value: &JsonValue) -> Result<Token> {
+		json_to_token(value, &self.param_type()?, self.components.as_deref()).map_err(|e| match e {
+			Error::InvalidParam(msg) if !self.name.is_empty() => {
+				Error::InvalidParam(format!("{}: {}", self.name, msg))
+			}
+			e => e,
+		})
+	}
+
+	pub fn param_type(&self) -> Result<ParamType> {
+		let suffixes = match self.r#type.strip_prefix("tuple") {
+			Some(suffixes) => suffixes,
+			None => {
+				return Reader::read(&self.r#type).map_err(|e| {
+					Error::ABIParseError(format!("type: {} is invalid, {}", self.r#type, e))
+				})
+			}
+		};
+
+		let components = self.components.iter().flatten();
+		let mut param_type =
+			ParamType::Tuple(components.map(Variable::param_type).collect::<Result<_>>()?);
+		// `tuple[2][]` is a dynamic array of fixed arrays, wrap from the left.
+		for suffix in suffixes.split_inclusive(']') {
+			param_type = match suffix.strip_prefix('[').and_then(|s| s.strip_suffix(']')) {
+				Some("") => ParamType::Array(Box::new(param_type)),
+				Some(len) => match len.parse() {
+					Ok(len) => ParamType::FixedArray(Box::new(param_type), len),
+					Err(_) => return Err(Error::ABIParseError(format!("type: {}", self.r#type))),
+				},
+				None => return Err(Error::ABIParseError(format!("type: {}", self.r#type))),
+			};
 		}
+		Ok(param_type)
 	}
+}
 
-	fn string_to_token(token_str: &String, variable: &Variable) -> Result<Token> {
-		match &variable.r#type {
-			v_type if v_type.starts_with("address") => token_str
-				.parse::<H160>()
-				.map(|token| token.into_token())
-				.map_err(|e| Error::AnyError(e.into())),
-			v_type if v_type.starts_with("uint256") => U256::from_dec_str(token_str)
-				.map(|token| token.into_token())
-				.map_err(|e| Error::AnyError(e.into())),
-			v_type if v_type.starts_with("bytes") => Ok(token_str.as_bytes().to_vec().into_token()),
-			_ => Err(Error::InvalidParam(format!(
-				"convert {} to type: {} failed",
-				token_str, variable.r#type
-			))),
+pub(crate) fn json_to_token(
+	value: &JsonValue,
+	kind: &ParamType,
+	components: Option<&[Variable]>,
+) -> Result<Token> {
+	let invalid = || Error::InvalidParam(format!("{} is not a valid {}", value, kind));
+	let token = match (kind, value) {
+		(ParamType::Address, JsonValue::String(s)) => {
+			Token::Address(s.trim_start_matches("0x").parse().map_err(|_| invalid())?)
 		}
+		(ParamType::Bool, JsonValue::Bool(b)) => Token::Bool(*b),
+		(ParamType::Bool, JsonValue::String(s)) => Token::Bool(s.parse().map_err(|_| invalid())?),
+		(ParamType::String, JsonValue::String(s)) => Token::String(s.clone()),
+		(ParamType::Bytes, JsonValue::String(s)) => {
+			Token::Bytes(decode_hex(s).ok_or_else(invalid)?)
+		}
+		(ParamType::FixedBytes(len), JsonValue::String(s)) => match decode_hex(s) {
+			Some(bytes) if bytes.len() == *len => Token::FixedBytes(bytes),
+			_ => return Err(invalid()),
+		},
+		(ParamType::Uint(bits), _) => match parse_integer(value) {
+			Some((false, num)) if *bits == 256 || num < U256::one() << *bits => Token::Uint(num),
+			_ => return Err(invalid()),
+		},
+		(ParamType::Int(bits), _) => {
+			let limit = U256::one() << (*bits - 1);
+			match parse_integer(value) {
+				Some((false, num)) if num < limit => Token::Int(num),
+				// Two's complement, sign extended to 256 bits.
+				Some((true, num)) if num <= limit => Token::Int((!num).overflowing_add(1.into()).0),
+				_ => return Err(invalid()),
+			}
+		}
+		(ParamType::Array(inner), JsonValue::Array(arr)) => Token::Array(
+			arr.iter().map(|item| json_to_token(item, inner, components)).collect::<Result<_>>()?,
+		),
+		(ParamType::FixedArray(inner, len), JsonValue::Array(arr)) if arr.len() == *len => {
+			Token::FixedArray(
+				arr.iter()
+					.map(|item| json_to_token(item, inner, components))
+					.collect::<Result<_>>()?,
+			)
+		}
+		(ParamType::Tuple(types), JsonValue::Array(arr)) if arr.len() == types.len() => {
+			Token::Tuple(
+				arr.iter()
+					.zip(types)
+					.enumerate()
+					.map(|(idx, (item, kind))| {
+						let components = components.and_then(|c| c.get(idx));
+						json_to_token(item, kind, components.and_then(|c| c.components.as_deref()))
+					})
+					.collect::<Result<_>>()?,
+			)
+		}
+		(ParamType::Tuple(_), JsonValue::Object(map)) => match components {
+			Some(components) => Token::Tuple(
+				components
+					.iter()
+					.map(|component| match map.get(&component.name) {
+						Some(item) => component.to_token(item),
+						None => Err(Error::InvalidParam(format!(
+							"member: {} is missing",
+							component.name
+						))),
+					})
+					.collect::<Result<_>>()?,
+			),
+			None => return Err(invalid()),
+		},
+		_ => return Err(invalid()),
+	};
+	Ok(token)
+}
+
+/// Sign and magnitude of a json number or a decimal or `0x` hex string.
+fn parse_integer(value: &JsonValue) -> Option<(bool, U256)> {
+	match value {
+		JsonValue::Number(num) => match (num.as_u64(), num.as_i64()) {
+			(Some(n), _) => Some((false, n.into())),
+			(None, Some(n)) => Some((true, n.unsigned_abs().into())),
+			_ => None,
+		},
+		JsonValue::String(s) => {
+			let (negative, s) = match s.trim().strip_prefix('-') {
+				Some(s) => (true, s),
+				None => (false, s.trim()),
+			};
+			let num = match s.strip_prefix("0x") {
+				Some(hex) => U256::from_str_radix(hex, 16).ok()?,
+				None if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) => {
+					U256::from_dec_str(s).ok()?
+				}
+				None => return None,
+			};
+			Some((negative, num))
+		}
+		_ => None,
 	}
 }
 
+fn decode_hex(s: &str) -> Option<Vec<u8>> {
+	hex::decode(s.strip_prefix("0x")?).ok()
+}
+
+/// Json object of decoded values keyed by variable name, or by position for unnamed ones.
+pub fn tokens_to_json(variables: &[Variable], tokens: Vec<Token>) -> Map<String, JsonValue> {
+	variables
+		.iter()
+		.zip(tokens)
+		.enumerate()
+		.map(|(idx, (variable, token))| {
+			let name =
+				if variable.name.is_empty() { idx.to_string() } else { variable.name.clone() };
+			(name, variable.to_json(token))
+		})
+		.collect()
+}
+
+/// Numbers become decimal strings since they don't fit into json numbers.
+fn token_to_json(token: Token, components: Option<&[Variable]>) -> JsonValue {
+	match token {
+		Token::Bool(b) => JsonValue::Bool(b),
+		Token::String(s) => JsonValue::String(s),
+		Token::Address(address) => JsonValue::String(to_checksum(&address)),
+		Token::Bytes(bytes) | Token::FixedBytes(bytes) => {
+			JsonValue::String(format!("0x{}", hex::encode(bytes)))
+		}
+		Token::Uint(num) => JsonValue::String(num.to_string()),
+		// Two's complement, the sign bit is the highest one.
+		Token::Int(num) if num.bit(255) => {
+			JsonValue::String(format!("-{}", (!num).overflowing_add(1.into()).0))
+		}
+		Token::Int(num) => JsonValue::String(num.to_string()),
+		// The components describe the elements of arrays of structs.
+		Token::Array(tokens) | Token::FixedArray(tokens) => JsonValue::Array(
+			tokens.into_iter().map(|token| token_to_json(token, components)).collect(),
+		),
+		Token::Tuple(tokens) => match components {
+			Some(components) if components.len() == tokens.len() => {
+				JsonValue::Object(tokens_to_json(components, tokens))
+			}
+			_ => JsonValue::Array(
+				tokens.into_iter().map(|token| token_to_json(token, None)).collect(),
+			),
+		},
+	}
+}
+
+/// Mixed-case checksum encoding of EIP-55.
+pub fn to_checksum(address: &H160) -> String {
+	let addr = hex::encode(address.as_bytes());
+	let hash = keccak256(addr.as_bytes());
+	let checksummed: String = addr
+		.chars()
+		.enumerate()
+		.map(|(idx, c)| {
+			let nibble = if idx % 2 == 0 { hash[idx / 2] >> 4 } else { hash[idx / 2] & 0x0f };
+			if nibble >= 8 {
+				c.to_ascii_uppercase()
+			} else {
+				c
+			}
+		})
+		.collect();
+	format!("0x{}", checksummed)
+}
+
 impl FromStr for ABI {
 	type Err = crate::error::Error;
 
@@ -141,29 +468,41 @@ impl FromStr for ABI {
 		let unit_list = serde_json::from_str::<Vec<ABIUnit>>(s)
 			.map_err(|e| Self::Err::ABIParseError(e.to_string()))?;
 
-		let mut type_map = unit_list.into_iter().fold(HashMap::new(), |mut map, unit| {
-			map.entry(unit.r#type).or_insert_with(|| vec![]).push(unit);
-			map
-		});
+		let mut type_map =
+			unit_list.into_iter().fold(HashMap::<_, Vec<_>>::new(), |mut map, unit| {
+				map.entry(unit.r#type).or_default().push(unit);
+				map
+			});
 		let constructor = type_map.remove(&UnitType::CONSTRUCTOR).map(|mut units| units.remove(0));
 
-		let functions = type_map.remove(&UnitType::FUNCTION).map_or(HashMap::new(), |units| {
-			units
-				.into_iter()
-				.map(|unit| (unit.name.clone().unwrap_or("".to_string()), unit))
-				.collect::<HashMap<String, ABIUnit>>()
-		});
+		let functions = type_map.remove(&UnitType::FUNCTION).unwrap_or_default().into_iter().fold(
+			HashMap::<_, Vec<_>>::new(),
+			|mut map, unit| {
+				map.entry(unit.name.clone().unwrap_or_default()).or_default().push(unit);
+				map
+			},
+		);
+
+		let errors = type_map.remove(&UnitType::ERROR).unwrap_or_default();
+		let events = type_map.remove(&UnitType::EVENT).unwrap_or_default();
 
-		let abi = ABI { constructor: constructor, function_map: functions };
+		let receive = type_map.remove(&UnitType::RECEIVE).map(|mut units| units.remove(0));
+		let fallback = type_map.remove(&UnitType::FALLBACK).map(|mut units| units.remove(0));
+
+		let abi = ABI { constructor, function_map: functions, errors, events, receive, fallback };
 		Ok(abi)
 	}
 }
 
 #[cfg(test)]
 mod tests {
-	use web3::types::U256;
+	use web3::{
+		ethabi::{encode, Token},
+		signing::keccak256,
+		types::{H160, H256, U256},
+	};
 
-	use crate::contracts::ABI;
+	use crate::contracts::{to_checksum, ABI};
 
 	#[test]
 	fn test_create_abi() {
@@ -178,4 +517,128 @@ mod tests {
 		println!("{}", amount.checked_div(U256::exp10(18)).unwrap().as_u128());
 		println!("{}", u128::MAX);
 	}
+
+	#[test]
+	fn test_outputs_to_json() {
+		let s = r#"[{"inputs":[{"name":"_txIndex","type":"uint256"}],"name":"getTransaction","outputs":[{"components":[{"name":"to","type":"address"},{"name":"value","type":"int256"},{"name":"data","type":"bytes"}],"name":"","type":"tuple"},{"name":"owners","type":"address[]"}],"stateMutability":"view","type":"function"}]"#;
+		let abi = s.parse::<ABI>().unwrap();
+		let address: H160 = "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed".parse().unwrap();
+		assert_eq!(to_checksum(&address), "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed");
+
+		let tokens = vec![
+			Token::Tuple(vec![
+				Token::Address(address),
+				Token::Int(U256::MAX),
+				Token::Bytes(vec![0xca, 0xfe]),
+			]),
+			Token::Array(vec![Token::Address(address)]),
+		];
+		let json = abi.function_map["getTransaction"][0].outputs_to_json(tokens);
+		assert_eq!(json["0"]["to"], "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed");
+		assert_eq!(json["0"]["value"], "-1");
+		assert_eq!(json["0"]["data"], "0xcafe");
+		assert_eq!(json["owners"][0], "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed");
+	}
+
+	#[test]
+	fn test_to_params() {
+		let s = r#"[{"inputs":[{"name":"num","type":"int64"},{"name":"small","type":"uint8"},{"name":"flag","type":"bool"},{"name":"data","type":"bytes"},{"name":"key","type":"bytes2"},{"name":"grid","type":"uint16[2][]"},{"components":[{"name":"to","type":"address"},{"name":"value","type":"uint256"}],"name":"txs","type":"tuple[]"}],"name":"store","outputs":[],"stateMutability":"nonpayable","type":"function"}]"#;
+		let abi = s.parse::<ABI>().unwrap();
+		let store = &abi.function_map["store"][0];
+		let to = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+		let params = serde_json::json!({
+			"num": -2, "small": "255", "flag": true, "data": "0xcafe", "key": "0xbeef",
+			"grid": [[1, 2], [3, "0x04"]],
+			"txs": [{"to": to, "value": "1000000000000000000000"}, [to, 1]],
+		});
+		let tokens = store.to_params(&params).unwrap();
+		assert_eq!(tokens[0], Token::Int(U256::MAX - 1));
+		assert_eq!(tokens[1], Token::Uint(255.into()));
+		assert_eq!(tokens[3], Token::Bytes(vec![0xca, 0xfe]));
+		assert_eq!(tokens[5].clone().into_array().unwrap().len(), 2);
+		assert_eq!(
+			tokens[6].clone().into_array().unwrap()[1],
+			Token::Tuple(vec![Token::Address(to.parse().unwrap()), Token::Uint(1.into())])
+		);
+
+		let names = ["num", "small", "flag", "data", "key", "grid", "txs"];
+		let mut positional = names.iter().map(|name| params[*name].clone()).collect::<Vec<_>>();
+		assert_eq!(store.to_params(&positional.clone().into()).unwrap(), tokens);
+		for invalid in [serde_json::json!("256"), serde_json::json!(-1)] {
+			positional[1] = invalid;
+			assert!(store.to_params(&positional.clone().into()).is_err());
+		}
+		positional[1] = serde_json::json!(1);
+		positional[0] = serde_json::json!("-9223372036854775809");
+		assert!(store.to_params(&positional.clone().into()).is_err());
+		positional[0] = serde_json::json!(1);
+		positional[4] = serde_json::json!("0xbe");
+		assert!(store.to_params(&positional.into()).is_err());
+	}
+
+	#[test]
+	fn test_single_array_param() {
+		let s = r#"[{"inputs":[{"name":"_owners","type":"address[]"}],"name":"setOwners","outputs":[],"stateMutability":"nonpayable","type":"function"}]"#;
+		let abi = s.parse::<ABI>().unwrap();
+		let set_owners = &abi.function_map["setOwners"][0];
+		let owner = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+		let tokens = vec![Token::Array(vec![Token::Address(owner.parse().unwrap())])];
+		assert_eq!(set_owners.to_params(&serde_json::json!([owner])).unwrap(), tokens);
+		assert_eq!(set_owners.to_params(&serde_json::json!([[owner]])).unwrap(), tokens);
+		assert_eq!(
+			set_owners.to_params(&serde_json::json!({ "_owners": [owner] })).unwrap(),
+			tokens
+		);
+		assert_eq!(
+			set_owners.to_params(&serde_json::json!([])).unwrap(),
+			vec![Token::Array(vec![])]
+		);
+	}
+
+	#[test]
+	fn test_resolve_overloads() {
+		let s = r#"[{"inputs":[{"name":"to","type":"address"},{"name":"amount","type":"uint256"}],"name":"transfer","outputs":[],"stateMutability":"nonpayable","type":"function"},{"inputs":[{"name":"to","type":"address"},{"name":"amount","type":"uint256"},{"name":"memo","type":"string"}],"name":"transfer","outputs":[],"stateMutability":"nonpayable","type":"function"},{"inputs":[{"name":"id","type":"uint256"}],"name":"get","outputs":[],"stateMutability":"view","type":"function"},{"inputs":[{"name":"key","type":"string"}],"name":"get","outputs":[],"stateMutability":"view","type":"function"}]"#;
+		let abi = s.parse::<ABI>().unwrap();
+		assert_eq!(abi.function_map["transfer"].len(), 2);
+
+		let to = "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed";
+		let (unit, _) = abi.resolve("transfer", &serde_json::json!([to, 1, "rent"])).unwrap();
+		assert_eq!(unit.signature().unwrap(), "transfer(address,uint256,string)");
+		let (unit, _) =
+			abi.resolve("transfer(address, uint256)", &serde_json::json!([to, 1])).unwrap();
+		assert_eq!(unit.signature().unwrap(), "transfer(address,uint256)");
+		// transfer(address,uint256)
+		let (unit, _) = abi.resolve("0xa9059cbb", &serde_json::json!([to, 1])).unwrap();
+		assert_eq!(unit.inputs.as_ref().unwrap().len(), 2);
+
+		let (unit, _) = abi.resolve("get", &serde_json::json!(["key"])).unwrap();
+		assert_eq!(unit.signature().unwrap(), "get(string)");
+		// A number string is valid for both.
+		assert!(abi.resolve("get", &serde_json::json!(["7"])).is_err());
+		assert!(abi.resolve("transfer", &serde_json::json!([to])).is_err());
+		assert!(abi.resolve("missing", &serde_json::Value::Null).is_err());
+	}
+
+	#[test]
+	fn test_decode_log() {
+		let abi = r#"[{"anonymous": false, "name": "Submit", "type": "event", "inputs": [
+			{"indexed": true, "name": "id", "type": "uint256"},
+			{"indexed": true, "name": "memo", "type": "string"},
+			{"indexed": false, "name": "to", "type": "address"}]}]"#
+			.parse::<ABI>()
+			.unwrap();
+		let topics = [
+			H256(keccak256(b"Submit(uint256,string,address)")),
+			H256::from_low_u64_be(7),
+			H256::repeat_byte(0xab),
+		];
+		let data = encode(&[Token::Address(H160::repeat_byte(0x11))]);
+
+		let log = abi.decode_log(&topics, &data).unwrap();
+		assert_eq!(log["event"], "Submit(uint256,string,address)");
+		assert_eq!(log["params"]["id"], "7");
+		assert_eq!(log["params"]["memo"], format!("{:?}", H256::repeat_byte(0xab)));
+		assert_eq!(log["params"]["to"], to_checksum(&H160::repeat_byte(0x11)));
+		assert!(abi.decode_log(&topics[1..], &data).is_none());
+	}
 }
@@ -0,0 +1,520 @@
+use std::{
+	collections::HashMap,
+	fs,
+	path::{Path, PathBuf},
+	sync::{Arc, Mutex, RwLock},
+	time::{Duration, SystemTime},
+};
+
+use log::{debug, info, warn};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value as JsonValue};
+use utoipa::ToSchema;
+
+use super::ABI;
+use crate::{
+	config::ContractsConfig,
+	error::Error::{AnyError, Conflict, InvalidParam, NotFound},
+	Result,
+};
+
+static REGISTRY: OnceCell<Arc<Registry>> = OnceCell::new();
+
+/// Subdirectory of the store with the outputs of the compiler.
+pub(crate) const COMPILED_DIR: &str = "compiled";
+
+/// A compiled contract, ready to be deployed and called.
+#[derive(Debug)]
+pub struct Artifact {
+	pub name: String,
+	pub abi: ABI,
+	/// The abi as json, web3 parses it again for deployments.
+	pub abi_json: String,
+	/// Hex creation code without `0x`, missing for interfaces and abstract contracts.
+	pub bytecode: Option<String>,
+	/// Compiler metadata or whatever the uploader attached.
+	pub metadata: Option<JsonValue>,
+	pub source: PathBuf,
+	/// Uploaded through the api rather than shipped in the artifacts directory or compiled.
+	pub uploaded: bool,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub(crate) struct UploadContractRequest {
+	name: String,
+	/// The abi as json array or as string.
+	abi: JsonValue,
+	/// Hex creation code, leave out for interfaces.
+	bytecode: Option<String>,
+	metadata: Option<JsonValue>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct ContractSummary {
+	name: String,
+	deployable: bool,
+	uploaded: bool,
+	functions: Vec<String>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct ContractDetail {
+	name: String,
+	abi: JsonValue,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	bytecode: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	metadata: Option<JsonValue>,
+	uploaded: bool,
+}
+
+/// A contract found in an artifact file, not validated yet.
+struct RawArtifact {
+	name: String,
+	abi: JsonValue,
+	bytecode: Option<String>,
+	metadata: Option<JsonValue>,
+}
+
+/// Artifacts of the artifacts directory and the uploads by contract name, reloaded when their
+/// files change.
+#[derive(Debug)]
+pub struct Registry {
+	dir: PathBuf,
+	store_dir: PathBuf,
+	artifacts: RwLock<HashMap<String, Arc<Artifact>>>,
+	fingerprint: Mutex<Vec<(PathBuf, SystemTime, u64)>>,
+}
+
+impl Registry {
+	fn new(dir: PathBuf, store_dir: PathBuf) -> Registry {
+		Registry { dir, store_dir, artifacts: RwLock::default(), fingerprint: Mutex::default() }
+	}
+
+	fn get(&self, name: &str) -> Option<Arc<Artifact>> {
+		self.artifacts.read().unwrap().get(name).cloned()
+	}
+
+	/// Reload all artifacts if any file of the directory was added, removed or modified.
+	fn reload(&self) {
+		// Fresh compilations take precedence over the shipped artifacts of the same contract.
+		let compiled_dir = self.store_dir.join(COMPILED_DIR);
+		let mut files = list_files(&compiled_dir);
+		for file in list_files(&self.dir).into_iter().chain(list_files(&self.store_dir)) {
+			if !files.contains(&file) {
+				files.push(file);
+			}
+		}
+		let fingerprint = files
+			.iter()
+			.filter_map(|path| {
+				let meta = fs::metadata(path).ok()?;
+				Some((path.clone(), meta.modified().ok()?, meta.len()))
+			})
+			.collect::<Vec<_>>();
+		{
+			let mut current = self.fingerprint.lock().unwrap();
+			if *current == fingerprint {
+				return;
+			}
+			*current = fingerprint;
+		}
+
+		let mut artifacts = HashMap::new();
+		for path in &files {
+			let uploaded = path.starts_with(&self.store_dir) && !path.starts_with(&compiled_dir);
+			for artifact in load_file(path, uploaded) {
+				match artifacts.get(&artifact.name) {
+					Some(Artifact { source, .. }) => warn!(
+						"Contract {} of {} is already loaded from {}, skipped",
+						artifact.name,
+						path.display(),
+						source.display()
+					),
+					None => {
+						artifacts.insert(artifact.name.clone(), artifact);
+					}
+				}
+			}
+		}
+		info!(
+			"Loaded {} contract artifacts from {} and {}",
+			artifacts.len(),
+			self.dir.display(),
+			self.store_dir.display()
+		);
+		*self.artifacts.write().unwrap() =
+			artifacts.into_iter().map(|(name, artifact)| (name, Arc::new(artifact))).collect();
+	}
+
+	fn upload(&self, request: UploadContractRequest) -> Result<()> {
+		if !is_valid_name(&request.name) {
+			return Err(InvalidParam(format!("contract name: {} is invalid", request.name)));
+		}
+		if let Some(artifact) = self.get(&request.name) {
+			self.check_uploaded(&artifact)?;
+		}
+
+		let abi = match request.abi {
+			JsonValue::String(abi) => serde_json::from_str(&abi)
+				.map_err(|e| InvalidParam(format!("abi is not json, {}", e)))?,
+			abi => abi,
+		};
+		abi.to_string().parse::<ABI>()?;
+		let bytecode =
+			request.bytecode.map(|code| code.trim().trim_start_matches("0x").to_string());
+		if let Some(code) = &bytecode {
+			hex::decode(code).map_err(|e| InvalidParam(format!("bytecode is not hex, {}", e)))?;
+		}
+
+		// Hardhat's layout, so that the store can be read like any artifacts directory.
+		let content = json!({
+			"contractName": request.name,
+			"abi": abi,
+			"bytecode": bytecode.map(|code| format!("0x{}", code)).unwrap_or_default(),
+			"metadata": request.metadata,
+		});
+		let path = self.store_dir.join(format!("{}.json", request.name));
+		write_file(&path, &content.to_string()).map_err(|e| AnyError(e.into()))?;
+		info!("Uploaded contract {} to {}", request.name, path.display());
+
+		self.reload();
+		Ok(())
+	}
+
+	fn delete(&self, name: &str) -> Result<()> {
+		if !is_valid_name(name) {
+			return Err(InvalidParam(format!("contract name: {} is invalid", name)));
+		}
+		let artifact = self.get(name).ok_or_else(|| NotFound(format!("contract: {}", name)))?;
+		self.check_uploaded(&artifact)?;
+		fs::remove_file(&artifact.source).map_err(|e| AnyError(e.into()))?;
+		info!("Deleted contract {} from {}", name, artifact.source.display());
+
+		self.reload();
+		Ok(())
+	}
+
+	/// Only uploads may be replaced or deleted, a compiled output holds every contract of its
+	/// source and is only written by the compiler.
+	fn check_uploaded(&self, artifact: &Artifact) -> Result<()> {
+		if artifact.uploaded {
+			return Ok(());
+		}
+		let origin = if artifact.source.starts_with(self.store_dir.join(COMPILED_DIR)) {
+			"compiled from a source"
+		} else {
+			"shipped in the artifacts directory"
+		};
+		Err(Conflict(format!("contract: {} is {}", artifact.name, origin)))
+	}
+
+	async fn watch(self: Arc<Self>, interval: Duration) {
+		let mut ticker = tokio::time::interval(interval);
+		loop {
+			ticker.tick().await;
+			let registry = self.clone();
+			let _ = tokio::task::spawn_blocking(move || registry.reload()).await;
+		}
+	}
+}
+
+/// Load the artifacts of the configured directory and watch it for changes.
+pub fn init(config: &ContractsConfig) -> Result<()> {
+	if !config.artifacts_dir.is_dir() {
+		warn!("Artifacts directory {} does not exist", config.artifacts_dir.display());
+	}
+	let registry = Arc::new(Registry::new(config.artifacts_dir.clone(), config.store_dir.clone()));
+	registry.reload();
+	if config.reload_secs > 0 {
+		tokio::spawn(registry.clone().watch(Duration::from_secs(config.reload_secs)));
+	}
+	let _ = REGISTRY.set(registry);
+	Ok(())
+}
+
+/// The artifact of a contract, names never reach the file system.
+pub fn artifact(name: &str) -> Result<Arc<Artifact>> {
+	if !is_valid_name(name) {
+		return Err(InvalidParam(format!("contract name: {} is invalid", name)));
+	}
+	REGISTRY
+		.get()
+		.and_then(|registry| registry.get(name))
+		.ok_or_else(|| NotFound(format!("contract: {}", name)))
+}
+
+pub(crate) fn list() -> Vec<ContractSummary> {
+	let mut contracts = registry()
+		.artifacts
+		.read()
+		.unwrap()
+		.values()
+		.map(|artifact| {
+			let mut functions = artifact
+				.abi
+				.function_map
+				.values()
+				.flatten()
+				.filter_map(|unit| unit.signature().ok())
+				.collect::<Vec<_>>();
+			functions.sort();
+			ContractSummary {
+				name: artifact.name.clone(),
+				deployable: artifact.bytecode.is_some(),
+				uploaded: artifact.uploaded,
+				functions,
+			}
+		})
+		.collect::<Vec<_>>();
+	contracts.sort_by(|a, b| a.name.cmp(&b.name));
+	contracts
+}
+
+pub(crate) fn detail(name: &str) -> Result<ContractDetail> {
+	let artifact = artifact(name)?;
+	Ok(ContractDetail {
+		name: artifact.name.clone(),
+		abi: serde_json::from_str(&artifact.abi_json).unwrap_or_default(),
+		bytecode: artifact.bytecode.as_ref().map(|code| format!("0x{}", code)),
+		metadata: artifact.metadata.clone(),
+		uploaded: artifact.uploaded,
+	})
+}
+
+/// Persist an uploaded artifact to the store, replacing an earlier upload of the same name.
+pub(crate) async fn upload(request: UploadContractRequest) -> Result<ContractDetail> {
+	let name = request.name.clone();
+	tokio::task::spawn_blocking(move || registry().upload(request))
+		.await
+		.map_err(|e| AnyError(e.into()))??;
+	detail(&name)
+}
+
+/// Remove an uploaded artifact, the ones of the artifacts directory stay.
+pub(crate) async fn delete(name: String) -> Result<()> {
+	tokio::task::spawn_blocking(move || registry().delete(&name))
+		.await
+		.map_err(|e| AnyError(e.into()))?
+}
+
+/// Pick up changed artifacts right away instead of at the next check.
+pub(crate) fn refresh() {
+	if let Some(registry) = REGISTRY.get() {
+		registry.reload();
+	}
+}
+
+fn registry() -> &'static Registry {
+	REGISTRY.get().expect("contract registry is not initialized")
+}
+
+/// Solidity identifiers only.
+fn is_valid_name(name: &str) -> bool {
+	name.chars().next().is_some_and(|c| !c.is_ascii_digit())
+		&& name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '$')
+}
+
+/// Write through a temporary file of its own, so that readers and concurrent writers of the
+/// same path never see a partial file.
+pub(crate) fn write_file(path: &Path, content: &str) -> std::io::Result<()> {
+	let tmp = path.with_extension(format!("json.{:016x}.tmp", rand::random::<u64>()));
+	fs::create_dir_all(path.parent().unwrap_or(Path::new(".")))?;
+	fs::write(&tmp, content).and_then(|_| fs::rename(&tmp, path)).inspect_err(|_| {
+		let _ = fs::remove_file(&tmp);
+	})
+}
+
+fn list_files(dir: &Path) -> Vec<PathBuf> {
+	let mut files = vec![];
+	for entry in fs::read_dir(dir).into_iter().flatten().flatten() {
+		let path = entry.path();
+		if path.is_dir() {
+			files.extend(list_files(&path));
+		} else {
+			files.push(path);
+		}
+	}
+	files.sort();
+	files
+}
+
+/// The artifacts of a `{name}.abi` + `{name}.bin` pair or a json artifact.
+fn load_file(path: &Path, uploaded: bool) -> Vec<Artifact> {
+	let stem = path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default();
+	let content = match path.extension().and_then(|ext| ext.to_str()) {
+		Some("abi" | "json") => match fs::read_to_string(path) {
+			Ok(content) => content,
+			Err(e) => {
+				warn!("Read artifact {} failed, {}", path.display(), e);
+				return vec![];
+			}
+		},
+		_ => return vec![],
+	};
+
+	let units = if path.extension().is_some_and(|ext| ext == "abi") {
+		let bytecode = fs::read_to_string(path.with_extension("bin")).ok();
+		match serde_json::from_str(&content) {
+			Ok(abi) => {
+				vec![RawArtifact { name: stem.to_string(), abi, bytecode, metadata: None }]
+			}
+			Err(e) => {
+				warn!("Artifact {} is invalid, {}", path.display(), e);
+				return vec![];
+			}
+		}
+	} else {
+		match serde_json::from_str(&content) {
+			Ok(json) => parse_json_artifact(stem, json),
+			Err(e) => {
+				warn!("Artifact {} is invalid, {}", path.display(), e);
+				return vec![];
+			}
+		}
+	};
+
+	units
+		.into_iter()
+		.filter_map(|RawArtifact { name, abi, bytecode, metadata }| {
+			if !is_valid_name(&name) {
+				warn!("Contract name {} of {} is invalid, skipped", name, path.display());
+				return None;
+			}
+			let abi_json = abi.to_string();
+			let abi = match abi_json.parse::<ABI>() {
+				Ok(abi) => abi,
+				Err(e) => {
+					warn!("Abi of {} in {} is invalid, {}", name, path.display(), e);
+					return None;
+				}
+			};
+			let bytecode = bytecode
+				.map(|code| code.trim().trim_start_matches("0x").to_string())
+				.filter(|code| !code.is_empty());
+			let source = path.to_path_buf();
+			Some(Artifact { name, abi, abi_json, bytecode, metadata, source, uploaded })
+		})
+		.collect()
+}
+
+/// Names, abis and bytecodes of a solc standard json output, a hardhat or a foundry artifact.
+fn parse_json_artifact(stem: &str, json: JsonValue) -> Vec<RawArtifact> {
+	let bytecode = |code: &JsonValue| match code {
+		// Hardhat
+		JsonValue::String(code) => Some(code.clone()),
+		// Foundry and solc
+		code => code["object"].as_str().map(|code| code.to_string()),
+	};
+
+	if let Some(sources) = json["contracts"].as_object() {
+		return sources
+			.values()
+			.filter_map(JsonValue::as_object)
+			.flatten()
+			.filter(|(_, contract)| contract["abi"].is_array())
+			.map(|(name, contract)| RawArtifact {
+				name: name.clone(),
+				abi: contract["abi"].clone(),
+				bytecode: bytecode(&contract["evm"]["bytecode"]),
+				metadata: contract.get("metadata").cloned(),
+			})
+			.collect();
+	}
+	if json["abi"].is_array() {
+		let name = json["contractName"].as_str().unwrap_or(stem).to_string();
+		return vec![RawArtifact {
+			name,
+			abi: json["abi"].clone(),
+			bytecode: bytecode(&json["bytecode"]),
+			metadata: json.get("metadata").filter(|metadata| !metadata.is_null()).cloned(),
+		}];
+	}
+
+	// Debug files, build infos and the like.
+	debug!("No contract in json {}", stem);
+	vec![]
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use axum::http::StatusCode;
+	use serde_json::json;
+
+	use super::{is_valid_name, parse_json_artifact, Registry, UploadContractRequest};
+
+	#[test]
+	fn test_parse_json_artifact() {
+		let abi = json!([{"inputs":[],"name":"retrieve","outputs":[],"stateMutability":"view","type":"function"}]);
+
+		let hardhat = json!({"contractName": "Storage", "abi": abi, "bytecode": "0x6080"});
+		let units = parse_json_artifact("Storage", hardhat);
+		assert_eq!(units[0].name, "Storage");
+		assert_eq!(units[0].bytecode.as_deref(), Some("0x6080"));
+
+		let foundry = json!({"abi": abi, "bytecode": {"object": "0x6080", "linkReferences": {}}});
+		let units = parse_json_artifact("Storage", foundry);
+		assert_eq!(units[0].bytecode.as_deref(), Some("0x6080"));
+
+		let solc = json!({"contracts": {"Storage.sol": {
+			"Storage": {"abi": abi, "evm": {"bytecode": {"object": "6080"}}},
+			"IStorage": {"abi": abi, "evm": {"bytecode": {"object": ""}}},
+		}}});
+		let mut units = parse_json_artifact("output", solc);
+		units.sort_by(|a, b| a.name.cmp(&b.name));
+		assert_eq!(
+			units.iter().map(|unit| unit.name.as_str()).collect::<Vec<_>>(),
+			["IStorage", "Storage"]
+		);
+
+		assert!(parse_json_artifact("Storage.dbg", json!({"buildInfo": "../x.json"})).is_empty());
+		assert!(is_valid_name("MultiSignWallet"));
+		assert!(!is_valid_name("../../etc/passwd"));
+		assert!(!is_valid_name(""));
+	}
+
+	#[test]
+	fn test_upload_and_delete() {
+		let dir = std::env::temp_dir().join(format!("registry-{}", std::process::id()));
+		let abi = json!([{"inputs":[],"name":"retrieve","outputs":[],"stateMutability":"view","type":"function"}]);
+		fs::create_dir_all(dir.join("artifacts")).unwrap();
+		fs::write(dir.join("artifacts/Shipped.abi"), abi.to_string()).unwrap();
+		let registry = Registry::new(dir.join("artifacts"), dir.join("store"));
+		registry.reload();
+		let request = |name: &str| UploadContractRequest {
+			name: name.to_string(),
+			abi: abi.clone(),
+			bytecode: Some("0x6080".to_string()),
+			metadata: None,
+		};
+		let status = |result: crate::Result<()>| result.unwrap_err().classify().0;
+
+		assert_eq!(status(registry.upload(request("../Storage"))), StatusCode::BAD_REQUEST);
+		assert_eq!(status(registry.upload(request("Shipped"))), StatusCode::CONFLICT);
+		registry.upload(request("Storage")).unwrap();
+		registry.upload(request("Storage")).unwrap();
+		assert!(registry.get("Storage").unwrap().uploaded);
+		assert!(dir.join("store/Storage.json").is_file());
+		assert_eq!(fs::read_dir(dir.join("store")).unwrap().count(), 1);
+
+		assert_eq!(status(registry.delete("Shipped")), StatusCode::CONFLICT);
+
+		// Compiled outputs are neither uploads nor replaced by them.
+		let output = json!({"contracts": {"Auction.sol": {"Auction": {"abi": abi}}}});
+		fs::create_dir_all(dir.join("store/compiled")).unwrap();
+		fs::write(dir.join("store/compiled/Auction.json"), output.to_string()).unwrap();
+		registry.reload();
+		assert!(!registry.get("Auction").unwrap().uploaded);
+		assert_eq!(status(registry.upload(request("Auction"))), StatusCode::CONFLICT);
+		assert_eq!(status(registry.delete("Auction")), StatusCode::CONFLICT);
+
+		assert_eq!(status(registry.delete("Missing")), StatusCode::NOT_FOUND);
+		registry.delete("Storage").unwrap();
+		assert!(registry.get("Storage").is_none());
+		assert!(registry.get("Shipped").is_some());
+		fs::remove_dir_all(dir).unwrap();
+	}
+}
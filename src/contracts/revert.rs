@@ -0,0 +1,167 @@
+use std::fmt::{Display, Formatter};
+
+use serde::Serialize;
+use serde_json::{Map, Value as JsonValue};
+use web3::{
+	ethabi::{decode, ParamType},
+	types::U256,
+};
+
+use super::{tokens_to_json, ABI};
+
+/// Selector of the builtin `Error(string)`.
+const ERROR_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+/// Selector of the builtin `Panic(uint256)`.
+const PANIC_SELECTOR: [u8; 4] = [0x4e, 0x48, 0x7b, 0x71];
+
+/// Why a contract call reverted.
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum Revert {
+	/// `require(cond, "reason")` or `revert("reason")`.
+	Error { reason: String },
+	/// A failed `assert` or a runtime error like an overflow.
+	Panic { code: U256, description: String },
+	/// A custom `error` declared in the contract.
+	Custom { name: String, signature: String, args: Map<String, JsonValue> },
+	/// Revert data none of the known errors match.
+	Unknown { data: String },
+}
+
+impl Revert {
+	/// Decode the data returned by a reverted call against the contract abi.
+	pub fn decode(data: &[u8], abi: Option<&ABI>) -> Revert {
+		if data.len() < 4 {
+			return Revert::Unknown { data: format!("0x{}", hex::encode(data)) };
+		}
+
+		let (selector, payload) = data.split_at(4);
+		let decoded = match selector {
+			s if s == ERROR_SELECTOR => decode(&[ParamType::String], payload).ok().map(|tokens| {
+				Revert::Error { reason: tokens[0].clone().into_string().unwrap_or_default() }
+			}),
+			s if s == PANIC_SELECTOR => {
+				decode(&[ParamType::Uint(256)], payload).ok().map(|tokens| {
+					let code = tokens[0].clone().into_uint().unwrap_or_default();
+					Revert::Panic { code, description: panic_description(code).to_string() }
+				})
+			}
+			s => abi.and_then(|abi| Self::decode_custom(s, payload, abi)),
+		};
+		decoded.unwrap_or_else(|| Revert::Unknown { data: format!("0x{}", hex::encode(data)) })
+	}
+
+	/// Decode the revert out of a json rpc error, nodes put the revert data at different places.
+	pub fn from_rpc_error(err: &jsonrpc_core::Error, abi: Option<&ABI>) -> Revert {
+		match err.data.as_ref().and_then(find_revert_data) {
+			Some(data) => Self::decode(&data, abi),
+			// No data, some nodes only put the reason into the message.
+			None => {
+				match err.message.split_once("reverted: ").or(err.message.split_once("revert ")) {
+					Some((_, reason)) => Revert::Error { reason: reason.to_string() },
+					None => Revert::Unknown { data: "0x".to_string() },
+				}
+			}
+		}
+	}
+
+	fn decode_custom(selector: &[u8], payload: &[u8], abi: &ABI) -> Option<Revert> {
+		let unit = abi.errors.iter().find(|unit| unit.selector().is_ok_and(|s| s == selector))?;
+		let tokens = decode(&unit.input_types().ok()?, payload).ok()?;
+		let args = tokens_to_json(unit.inputs.as_deref().unwrap_or_default(), tokens);
+		Some(Revert::Custom {
+			name: unit.name.clone().unwrap_or_default(),
+			signature: unit.signature().ok()?,
+			args,
+		})
+	}
+}
+
+impl Display for Revert {
+	fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+		match self {
+			Revert::Error { reason } => write!(f, "Error({:?})", reason),
+			Revert::Panic { code, description } => {
+				write!(f, "Panic(0x{:x}: {})", code, description)
+			}
+			Revert::Custom { signature, args, .. } => {
+				write!(f, "{} {}", signature, JsonValue::Object(args.clone()))
+			}
+			Revert::Unknown { data } => write!(f, "unknown revert data: {}", data),
+		}
+	}
+}
+
+/// Geth puts the hex string into `data`, hardhat and ganache nest it into an object.
+fn find_revert_data(data: &JsonValue) -> Option<Vec<u8>> {
+	match data {
+		JsonValue::String(s) => s.strip_prefix("0x").and_then(|s| hex::decode(s).ok()),
+		JsonValue::Object(map) => ["data", "return", "result"]
+			.iter()
+			.filter_map(|key| map.get(*key))
+			.chain(map.values())
+			.find_map(find_revert_data),
+		_ => None,
+	}
+}
+
+fn panic_description(code: U256) -> &'static str {
+	match code.low_u64() {
+		0x00 => "generic compiler panic",
+		0x01 => "assertion failed",
+		0x11 => "arithmetic overflow or underflow",
+		0x12 => "division or modulo by zero",
+		0x21 => "invalid enum value",
+		0x22 => "invalid storage byte array encoding",
+		0x31 => "pop on empty array",
+		0x32 => "array index out of bounds",
+		0x41 => "out of memory",
+		0x51 => "call to zero-initialized function",
+		_ => "unknown panic code",
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use web3::ethabi::{encode, Token};
+
+	use super::Revert;
+	use crate::contracts::ABI;
+
+	#[test]
+	fn test_decode_revert() {
+		let mut data = vec![0x08, 0xc3, 0x79, 0xa0];
+		data.extend(encode(&[Token::String("highest bidder".to_string())]));
+		assert!(
+			matches!(Revert::decode(&data, None), Revert::Error { reason } if reason == "highest bidder")
+		);
+
+		let mut data = vec![0x4e, 0x48, 0x7b, 0x71];
+		data.extend(encode(&[Token::Uint(0x11.into())]));
+		assert!(
+			matches!(Revert::decode(&data, None), Revert::Panic { code, .. } if code == 0x11.into())
+		);
+
+		let abi = r#"[{"inputs":[],"name":"BidNotHighEnough","type":"error"},{"inputs":[{"internalType":"uint256","name":"available","type":"uint256"}],"name":"InsufficientBalance","type":"error"}]"#;
+		let abi = abi.parse::<ABI>().unwrap();
+		let data = abi.errors[0].selector().unwrap();
+		assert!(
+			matches!(Revert::decode(&data, Some(&abi)), Revert::Custom { name, .. } if name == "BidNotHighEnough")
+		);
+
+		let mut data = abi.errors[1].selector().unwrap().to_vec();
+		data.extend(encode(&[Token::Uint(7.into())]));
+		match Revert::decode(&data, Some(&abi)) {
+			Revert::Custom { signature, args, .. } => {
+				assert_eq!(signature, "InsufficientBalance(uint256)");
+				assert_eq!(args["available"], "7");
+			}
+			revert => panic!("unexpected revert: {:?}", revert),
+		}
+
+		assert!(matches!(
+			Revert::decode(&[0xde, 0xad, 0xbe, 0xef], Some(&abi)),
+			Revert::Unknown { .. }
+		));
+	}
+}
@@ -1,6 +1,19 @@
 use std::fmt::Debug;
 
+use axum::{
+	http::StatusCode,
+	response::{IntoResponse, Response},
+	Json,
+};
+use log::{error, warn};
 use thiserror::Error as ThisError;
+use web3::error::TransportError;
+
+use crate::{
+	contracts::{revert::Revert, ABI},
+	ethereum::transport::RPC_TIMEOUT_MSG,
+	routes::ResultInfo,
+};
 
 #[derive(ThisError, Debug)]
 pub enum Error {
@@ -10,12 +23,182 @@ pub enum Error {
 	#[error("call web3 api error: {0:?}")]
 	Web3ContractError(#[from] web3::contract::Error),
 
+	#[error("deploy contract error: {0}")]
+	Web3DeployError(#[from] web3::contract::deploy::Error),
+
+	#[error("contract reverted: {0}")]
+	ContractReverted(Revert),
+
+	#[error("compile solidity error: {0}")]
+	CompileError(String),
+
 	#[error("abi json is invalid, {0}")]
 	ABIParseError(String),
 
 	#[error("input parameter is invalid, {0}")]
 	InvalidParam(String),
 
+	#[error("{0} not found")]
+	NotFound(String),
+
+	#[error("conflict, {0}")]
+	Conflict(String),
+
+	#[error("config is invalid, {0}")]
+	ConfigError(String),
+
 	#[error(transparent)]
 	AnyError(#[from] anyhow::Error),
 }
+
+impl Error {
+	/// The http status and the machine readable error code of the response.
+	pub fn classify(&self) -> (StatusCode, &'static str) {
+		use web3::contract::{deploy, Error as ContractError};
+
+		match self {
+			Error::Web3Error(err)
+			| Error::Web3ContractError(ContractError::Api(err))
+			| Error::Web3DeployError(deploy::Error::Api(err)) => classify_web3(err),
+			Error::Web3ContractError(_) | Error::Web3DeployError(deploy::Error::Abi(_)) => {
+				(StatusCode::BAD_REQUEST, "INVALID_PARAM")
+			}
+			Error::Web3DeployError(_) => (StatusCode::UNPROCESSABLE_ENTITY, "DEPLOYMENT_FAILED"),
+			Error::ContractReverted(_) => (StatusCode::UNPROCESSABLE_ENTITY, "EXECUTION_REVERTED"),
+			Error::CompileError(_) => (StatusCode::UNPROCESSABLE_ENTITY, "COMPILATION_FAILED"),
+			Error::ABIParseError(_) => (StatusCode::BAD_REQUEST, "ABI_PARSE_ERROR"),
+			Error::InvalidParam(_) => (StatusCode::BAD_REQUEST, "INVALID_PARAM"),
+			Error::NotFound(_) => (StatusCode::NOT_FOUND, "NOT_FOUND"),
+			Error::Conflict(_) => (StatusCode::CONFLICT, "CONFLICT"),
+			Error::ConfigError(_) | Error::AnyError(_) => {
+				(StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR")
+			}
+		}
+	}
+
+	/// The json rpc error answered by the node, if any.
+	pub fn rpc_error(&self) -> Option<&jsonrpc_core::Error> {
+		use web3::contract::{deploy, Error as ContractError};
+
+		match self {
+			Error::Web3Error(web3::Error::Rpc(err))
+			| Error::Web3ContractError(ContractError::Api(web3::Error::Rpc(err)))
+			| Error::Web3DeployError(deploy::Error::Api(web3::Error::Rpc(err))) => Some(err),
+			_ => None,
+		}
+	}
+
+	/// Decode the revert reason of a reverted contract call, other errors stay as they are.
+	pub fn decode_revert(self, abi: &ABI) -> Error {
+		match self.rpc_error() {
+			Some(err) if is_revert(err) => {
+				Error::ContractReverted(Revert::from_rpc_error(err, Some(abi)))
+			}
+			_ => self,
+		}
+	}
+}
+
+impl IntoResponse for Error {
+	fn into_response(self) -> Response {
+		let (status, code) = self.classify();
+		if status.is_server_error() {
+			error!(target: "ethereum", "Request failed: {}", self);
+		} else {
+			warn!(target: "ethereum", "Request rejected: {}", self);
+		}
+		let data = match &self {
+			Error::ContractReverted(revert) => serde_json::to_value(revert).ok(),
+			_ => None,
+		};
+		let body = ResultInfo::error(status, code, self.to_string()).with_data(data);
+		(status, Json(body)).into_response()
+	}
+}
+
+fn classify_web3(err: &web3::Error) -> (StatusCode, &'static str) {
+	match err {
+		web3::Error::Rpc(rpc) if is_revert(rpc) => {
+			(StatusCode::UNPROCESSABLE_ENTITY, "EXECUTION_REVERTED")
+		}
+		web3::Error::Rpc(rpc) if rpc.code == jsonrpc_core::ErrorCode::InvalidParams => {
+			(StatusCode::BAD_REQUEST, "INVALID_PARAM")
+		}
+		web3::Error::Rpc(rpc) if rpc.message.contains("insufficient funds") => {
+			(StatusCode::BAD_REQUEST, "INSUFFICIENT_FUNDS")
+		}
+		web3::Error::Rpc(rpc) if rpc.message.contains("nonce too low") => {
+			(StatusCode::CONFLICT, "NONCE_TOO_LOW")
+		}
+		web3::Error::Rpc(rpc) if rpc.message.contains("replacement transaction underpriced") => {
+			(StatusCode::CONFLICT, "REPLACEMENT_UNDERPRICED")
+		}
+		web3::Error::Rpc(_) => (StatusCode::BAD_GATEWAY, "NODE_ERROR"),
+		web3::Error::Transport(TransportError::Message(msg)) if msg == RPC_TIMEOUT_MSG => {
+			(StatusCode::GATEWAY_TIMEOUT, "NODE_TIMEOUT")
+		}
+		web3::Error::Recovery(_) => (StatusCode::BAD_REQUEST, "INVALID_SIGNATURE"),
+		web3::Error::Internal => (StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR"),
+		_ => (StatusCode::BAD_GATEWAY, "NODE_UNAVAILABLE"),
+	}
+}
+
+/// Geth answers reverts with code 3, other nodes only start the message as geth does. Errors
+/// which merely mention a revert somewhere are not taken for one.
+fn is_revert(err: &jsonrpc_core::Error) -> bool {
+	err.code == jsonrpc_core::ErrorCode::ServerError(3)
+		|| err.message.starts_with("execution reverted")
+}
+
+#[cfg(test)]
+mod tests {
+	use axum::http::StatusCode;
+	use jsonrpc_core::ErrorCode;
+	use web3::error::TransportError;
+
+	use super::Error;
+	use crate::ethereum::transport::RPC_TIMEOUT_MSG;
+
+	#[test]
+	fn test_classify() {
+		let err = Error::InvalidParam("account".to_string());
+		assert_eq!(err.classify(), (StatusCode::BAD_REQUEST, "INVALID_PARAM"));
+
+		let revert = jsonrpc_core::Error {
+			code: ErrorCode::ServerError(3),
+			message: "execution reverted".to_string(),
+			data: None,
+		};
+		let err = Error::Web3ContractError(web3::contract::Error::Api(web3::Error::Rpc(revert)));
+		assert_eq!(err.classify(), (StatusCode::UNPROCESSABLE_ENTITY, "EXECUTION_REVERTED"));
+
+		let timeout = TransportError::Message(RPC_TIMEOUT_MSG.to_string());
+		let err = Error::Web3Error(web3::Error::Transport(timeout));
+		assert_eq!(err.classify(), (StatusCode::GATEWAY_TIMEOUT, "NODE_TIMEOUT"));
+
+		let rejected = |message: &str| {
+			Error::Web3Error(web3::Error::Rpc(jsonrpc_core::Error {
+				code: ErrorCode::ServerError(-32000),
+				message: message.to_string(),
+				data: None,
+			}))
+		};
+		let err = rejected("insufficient funds for gas * price + value");
+		assert_eq!(err.classify(), (StatusCode::BAD_REQUEST, "INSUFFICIENT_FUNDS"));
+		let err = rejected("nonce too low");
+		assert_eq!(err.classify(), (StatusCode::CONFLICT, "NONCE_TOO_LOW"));
+		let err = rejected("replacement transaction underpriced");
+		assert_eq!(err.classify(), (StatusCode::CONFLICT, "REPLACEMENT_UNDERPRICED"));
+		assert_eq!(
+			rejected("header not found").classify(),
+			(StatusCode::BAD_GATEWAY, "NODE_ERROR")
+		);
+		let err = rejected("execution reverted: not the owner");
+		assert_eq!(err.classify(), (StatusCode::UNPROCESSABLE_ENTITY, "EXECUTION_REVERTED"));
+		let err = rejected("debug_traceTransaction can't revert to the state of block 0x10");
+		assert_eq!(err.classify(), (StatusCode::BAD_GATEWAY, "NODE_ERROR"));
+
+		let err = Error::Web3Error(web3::Error::Unreachable);
+		assert_eq!(err.classify(), (StatusCode::BAD_GATEWAY, "NODE_UNAVAILABLE"));
+	}
+}
@@ -1,22 +1,60 @@
-use std::ops::Div;
-use web3::types::{H160, U256};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use web3::types::H160;
 
 use crate::{error::Error::InvalidParam, Result};
 
-use super::WEB3;
+use super::{
+	amount::{Amount, ETHER_DECIMALS},
+	Network,
+};
+
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct BalanceQuery {
+	/// Fractional digits of the formatted balance, 18 at most.
+	pub precision: Option<usize>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct Balance {
+	/// Exact balance in wei.
+	#[schema(value_type = String)]
+	wei: Amount,
+	/// Balance in ether, rounded down to the requested precision.
+	ether: String,
+}
+
+/// Fractional digits of formatted balances unless a precision is requested.
+const DEFAULT_PRECISION: usize = 6;
 
 #[inline]
-pub async fn account_balance(account_str: &str) -> Result<String> {
+pub async fn account_balance(
+	network: &Network,
+	account_str: &str,
+	query: BalanceQuery,
+) -> Result<Balance> {
 	let account = account_str
 		.parse()
 		.map_err(|_| InvalidParam(format!("account: {} parse failed", account_str)))?;
-	let balance = WEB3.eth().balance(account, None).await?;
-	let eth_amt = balance.div(U256::exp10(18));
-	Ok(eth_amt.to_string())
+	balance(network, account, &query).await
+}
+
+pub(crate) async fn balance(
+	network: &Network,
+	account: H160,
+	query: &BalanceQuery,
+) -> Result<Balance> {
+	let precision = query.precision.unwrap_or(DEFAULT_PRECISION);
+	if precision > ETHER_DECIMALS {
+		return Err(InvalidParam(format!("precision: {} exceeds {}", precision, ETHER_DECIMALS)));
+	}
+
+	let wei = Amount::from(network.web3.eth().balance(account, None).await?);
+	Ok(Balance { wei, ether: wei.format(ETHER_DECIMALS, precision) })
 }
 
 #[inline]
-pub async fn accounts() -> Result<Vec<H160>> {
-	let accounts = WEB3.eth().accounts().await?;
+pub async fn accounts(network: &Network) -> Result<Vec<H160>> {
+	let accounts = network.web3.eth().accounts().await?;
 	Ok(accounts)
 }
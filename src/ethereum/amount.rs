@@ -0,0 +1,127 @@
+use std::{fmt, str::FromStr};
+
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize, Serializer};
+use serde_json::Value as JsonValue;
+use web3::types::U256;
+
+use crate::error::Error::{self, InvalidParam};
+
+/// Decimals of the units an amount can be given in.
+const UNITS: [(&str, usize); 4] = [("wei", 0), ("gwei", 9), ("ether", 18), ("eth", 18)];
+
+pub(crate) const ETHER_DECIMALS: usize = 18;
+
+/// An exact amount of wei, parsed from a json number of wei or a string like `1.5 ether`,
+/// `200 gwei`, `1000` or `0x3e8`. Amounts which don't fit in 256 bits are rejected.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) struct Amount(U256);
+
+impl Amount {
+	pub(crate) fn wei(self) -> U256 {
+		self.0
+	}
+
+	/// Decimal string in a unit of `decimals`, rounded down to `precision` fractional digits.
+	pub(crate) fn format(self, decimals: usize, precision: usize) -> String {
+		let unit = U256::exp10(decimals);
+		let fraction = format!("{:0>width$}", (self.0 % unit).to_string(), width = decimals);
+		let fraction = fraction[..precision.min(decimals)].trim_end_matches('0');
+		match fraction.is_empty() {
+			true => (self.0 / unit).to_string(),
+			false => format!("{}.{}", self.0 / unit, fraction),
+		}
+	}
+}
+
+impl From<U256> for Amount {
+	fn from(wei: U256) -> Self {
+		Amount(wei)
+	}
+}
+
+impl FromStr for Amount {
+	type Err = Error;
+
+	fn from_str(amount: &str) -> Result<Self, Self::Err> {
+		let invalid = || InvalidParam(format!("amount: {} is invalid", amount));
+		let trimmed = amount.trim();
+		if let Some(hex) = trimmed.strip_prefix("0x") {
+			return U256::from_str_radix(hex, 16).map(Amount).map_err(|_| invalid());
+		}
+
+		let split = trimmed.find(|c: char| c.is_ascii_alphabetic()).unwrap_or(trimmed.len());
+		let (number, unit) = trimmed.split_at(split);
+		let decimals = match unit {
+			"" => 0,
+			unit => UNITS
+				.iter()
+				.find(|(name, _)| unit.eq_ignore_ascii_case(name))
+				.map(|(_, decimals)| *decimals)
+				.ok_or_else(invalid)?,
+		};
+		let (int, frac) = number.trim().split_once('.').unwrap_or((number.trim(), ""));
+		let digits = |digits: &str| digits.bytes().all(|b| b.is_ascii_digit());
+		if int.is_empty() && frac.is_empty()
+			|| !digits(int)
+			|| !digits(frac)
+			|| frac.len() > decimals
+		{
+			return Err(invalid());
+		}
+		U256::from_dec_str(&format!("{}{}{}", int, frac, "0".repeat(decimals - frac.len())))
+			.map(Amount)
+			.map_err(|_| invalid())
+	}
+}
+
+impl fmt::Display for Amount {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		write!(f, "{}", self.0)
+	}
+}
+
+impl Serialize for Amount {
+	fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+		serializer.collect_str(self)
+	}
+}
+
+impl<'de> Deserialize<'de> for Amount {
+	fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+		let value = JsonValue::deserialize(deserializer)?;
+		let amount = match &value {
+			JsonValue::Number(num) => num.as_u64().map(|wei| Amount(wei.into())),
+			JsonValue::String(s) => s.parse().ok(),
+			_ => None,
+		};
+		amount.ok_or_else(|| D::Error::custom(format!("{} is not a valid amount", value)))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use web3::types::U256;
+
+	use super::Amount;
+
+	#[test]
+	fn test_amount() {
+		let parse = |s: &str| s.parse::<Amount>().ok().map(Amount::wei);
+		assert_eq!(parse("1.5 ether"), Some(1_500_000_000_000_000_000u64.into()));
+		assert_eq!(parse("200gwei"), Some(200_000_000_000u64.into()));
+		assert_eq!(parse("1000"), Some(1000.into()));
+		assert_eq!(parse("0x3e8"), Some(1000.into()));
+		assert_eq!(parse("1.5 wei"), None);
+		assert_eq!(parse("1 finney"), None);
+		assert_eq!(parse("."), None);
+		assert_eq!(parse(&format!("{}0", U256::MAX)), None);
+		assert!(serde_json::from_str::<Amount>("-1").is_err());
+
+		let amount = Amount::from(U256::from(1_234_567_890_000_000_000u64));
+		assert_eq!(amount.format(18, 18), "1.23456789");
+		assert_eq!(amount.format(18, 4), "1.2345");
+		assert_eq!(amount.format(18, 0), "1");
+		assert_eq!(Amount::from(U256::from(5)).format(18, 6), "0");
+		assert_eq!(serde_json::to_string(&amount).unwrap(), r#""1234567890000000000""#);
+	}
+}
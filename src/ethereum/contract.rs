@@ -1,147 +1,230 @@
-use std::{fs::File, io::Read, path::Path, str::FromStr, time::Duration};
-
-use log::info;
+use log::{info, warn};
 use serde::{Deserialize, Serialize};
 use serde_json::Value as JsonValue;
 use utoipa::ToSchema;
 use web3::{
-	contract::{Contract, Options},
-	ethabi::Token,
-	types::{H160, H256},
+	ethabi::{decode, encode},
+	types::{CallRequest, TransactionRequest, H160, H256},
 };
 
 use crate::{
-	contracts::ABI,
+	contracts::{compiler, registry, ABIUnit},
 	error::Error::{self, *},
 };
 
-use crate::{ethereum::WEB3, Result};
-
-const CONTRACT_ABI_FORMAT: &str = "./src/contracts/{}.abi";
-const CONTRACT_BIN_FORMAT: &str = "./src/contracts/{}.bin";
+use super::{
+	amount::Amount,
+	deployment::{self, NewDeployment},
+	transaction::{
+		fill_gas, send_signed, sender,
+		signing::Signer,
+		tracker::{TxInfo, TxKind},
+		TxOptions,
+	},
+	Network,
+};
+use crate::Result;
 
 #[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
 pub(crate) struct InvokeContractRequest {
-	contract_name: String,
+	/// May be left out for contracts deployed by this service.
+	contract_name: Option<String>,
+	/// The address or the alias of a deployment.
 	contract_address: String,
+	/// Sender, the account of the signer when one is given.
 	from_account: Option<String>,
+	/// Alias or address of the signer the call is signed by, otherwise the node signs.
+	signer: Option<String>,
+	/// A function name, a full signature like `transfer(address,uint256)` or a 4-byte selector.
 	fn_name: String,
 	#[serde(default)]
 	fn_params: JsonValue,
+	#[serde(flatten)]
+	options: TxOptions,
 	#[serde(default)]
 	confirmations: usize,
 }
 
 #[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
 pub(crate) struct DeployContractRequest {
-	from_account: String,
-	contract_name: String,
+	/// Sender, the account of the signer when one is given.
+	pub from_account: Option<String>,
+	/// Alias or address of the signer the deployment is signed by, otherwise the node
+	/// signs.
+	pub signer: Option<String>,
+	pub contract_name: String,
+	/// Source file to compile before the deployment, like `Auction.sol`.
+	pub source: Option<String>,
 	#[serde(default)]
-	contract_params: JsonValue,
+	pub contract_params: JsonValue,
+	/// Name to call the deployment by instead of its address.
+	pub alias: Option<String>,
+	#[serde(flatten)]
+	pub options: TxOptions,
+	/// Blocks on top of the deployment before it is recorded.
 	#[serde(default)]
-	confirmations: usize,
+	pub confirmations: usize,
 }
 
-pub(crate) async fn deploy_sol_contract(request: DeployContractRequest) -> Result<H160> {
-	let account = request.from_account.parse().map_err(|_| InvalidParam(request.from_account))?;
-
-	let abi_url = CONTRACT_ABI_FORMAT.replace("{}", &request.contract_name);
-	let bin_url = CONTRACT_BIN_FORMAT.replace("{}", &request.contract_name);
-
-	let contract_abi = read_file(abi_url)?;
-	let contract_bin = read_file(bin_url)?;
-	let constructor = contract_abi.parse::<ABI>()?.constructor;
-	let params = if constructor.is_some() {
-		constructor.unwrap().to_params(&request.contract_params)?
-	} else {
-		vec![]
+pub(crate) async fn deploy_sol_contract(
+	network: &Network,
+	request: DeployContractRequest,
+) -> Result<TxInfo> {
+	let (account, signer) =
+		sender(request.from_account.as_deref(), request.signer.as_deref(), None)?;
+	if let Some(alias) = &request.alias {
+		deployment::check_alias(network, alias)?;
+	}
+
+	if let Some(source) = &request.source {
+		let compiled = compiler::compile(source).await?;
+		if !compiled.contracts.contains(&request.contract_name) {
+			return Err(NotFound(format!("contract: {} in {}", request.contract_name, source)));
+		}
+	}
+
+	let artifact = registry::artifact(&request.contract_name)?;
+	let abi = &artifact.abi;
+	let bytecode = artifact.bytecode.as_ref().ok_or_else(|| {
+		InvalidParam(format!("contract: {} has no bytecode", request.contract_name))
+	})?;
+	let mut data = hex::decode(bytecode).map_err(|e| {
+		InvalidParam(format!("bytecode of {} is not hex, {}", request.contract_name, e))
+	})?;
+	if let Some(constructor) = &abi.constructor {
+		data.extend(encode(&constructor.to_params(&request.contract_params)?));
+	}
+	let payable = abi.constructor.as_ref().is_some_and(ABIUnit::is_payable);
+	if !payable && request.options.value.is_some_and(|value| !value.wei().is_zero()) {
+		return Err(InvalidParam(format!(
+			"constructor of {} is not payable",
+			request.contract_name
+		)));
+	}
+
+	let mut tx =
+		TransactionRequest { from: account, data: Some(data.into()), ..Default::default() };
+	request.options.apply(&mut tx)?;
+	fill_gas(network, &mut tx).await.map_err(|e| e.decode_revert(abi))?;
+
+	// The address follows from the nonce, so it is settled before the deployment is sent. Node
+	// accounts reserve it as well, concurrent deployments would predict the same address.
+	let reservation = match tx.nonce {
+		Some(_) => None,
+		None => Some(network.nonces.reserve(network, account).await?),
 	};
-
-	let address = Contract::deploy(WEB3.eth(), contract_abi.as_bytes())
-		.map_err(|e| AnyError(e.into()))?
-		.confirmations(request.confirmations)
-		.poll_interval(Duration::from_secs(10))
-		.options(Options::with(|options| options.gas = Some(3_000_000.into())))
-		.execute(contract_bin, params.as_slice(), account)
-		.await
-		.map_err(|e| AnyError(e.into()))?
-		.address();
-
-	info!("Deploy value storage contract, account: {}, addr: {}", account, address);
-	Ok(address)
-}
-
-pub(crate) async fn call_sol_contract(request: InvokeContractRequest) -> Result<H256> {
-	let abi_url = CONTRACT_ABI_FORMAT.replace("{}", &request.contract_name);
-	let contract_abi = read_file(abi_url)?;
-	let params = parse_params(&contract_abi, &request)?;
-
-	let address =
-		request.contract_address.parse().map_err(|_| InvalidParam(request.contract_address))?;
-	let from_account = request
-		.from_account
-		.as_ref()
-		.unwrap()
-		.parse()
-		.map_err(|_| InvalidParam(request.from_account.unwrap()))?;
-
-	let receipt = Contract::from_json(WEB3.eth(), address, contract_abi.as_bytes())
-		.map_err(|e| Web3ContractError(e.into()))?
-		.call_with_confirmations(
-			&request.fn_name,
-			params.as_slice(),
-			from_account,
-			Options::default(),
-			request.confirmations,
-		)
-		.await?;
-	Ok(receipt.transaction_hash)
+	if let Some(reservation) = &reservation {
+		tx.nonce = Some(reservation.nonce);
+	}
+	let address = deployment::contract_address(account, tx.nonce.unwrap_or_default());
+	deployment::record(NewDeployment {
+		network: &network.name,
+		alias: request.alias.as_deref(),
+		contract_name: &request.contract_name,
+		address,
+		deployer: account,
+		constructor_args: &request.contract_params,
+	})?;
+
+	let hash = match submit(network, tx, signer.as_deref()).await {
+		Ok(hash) => hash,
+		Err(e) => {
+			deployment::discard(network, address)?;
+			return Err(e.decode_revert(abi));
+		}
+	};
+	if let Some(reservation) = reservation {
+		reservation.sent();
+	}
+
+	info!(
+		"Deploy contract {} on {}, account: {:?}, tx: {:?}, addr: {:?}",
+		request.contract_name, network.name, account, hash, address
+	);
+	let kind = TxKind::Deploy { contract_name: request.contract_name, address };
+	network.tracker.track(hash, account, kind, request.confirmations);
+	match deployment::sent(network, address, hash) {
+		Ok(pending) => network.tracker.set_deployment(hash, pending),
+		Err(e) => warn!("Deployment {:?} isn't recorded as sent, {}", address, e),
+	}
+	network.tracker.status(network, hash).await
 }
 
-pub(crate) async fn query_sol_contract(request: InvokeContractRequest) -> Result<Vec<String>> {
-	let abi_url = CONTRACT_ABI_FORMAT.replace("{}", &request.contract_name);
-	let contract_abi = read_file(abi_url)?;
-	let params = parse_params(&contract_abi, &request)?;
-
-	let address = H160::from_str(&request.contract_address)
-		.map_err(|_| web3::Error::Decoder(request.contract_address))?;
-	let from = if request.from_account.is_some() {
-		Some(
-			request
-				.from_account
-				.as_ref()
-				.unwrap()
-				.parse::<H160>()
-				.map_err(|_| web3::Error::Decoder(request.from_account.unwrap()))?,
-		)
-	} else {
-		None
+pub(crate) async fn call_sol_contract(
+	network: &Network,
+	request: InvokeContractRequest,
+) -> Result<H256> {
+	let (contract_name, address) =
+		deployment::resolve(network, request.contract_name.as_deref(), &request.contract_address)?;
+	let artifact = registry::artifact(&contract_name)?;
+	let abi = &artifact.abi;
+	let (function, params) = abi.resolve(&request.fn_name, &request.fn_params)?;
+
+	if !function.is_payable() && request.options.value.is_some_and(|value| !value.wei().is_zero()) {
+		return Err(InvalidParam(format!("function: {} is not payable", function.signature()?)));
+	}
+
+	let (from_account, signer) =
+		sender(request.from_account.as_deref(), request.signer.as_deref(), None)?;
+
+	let mut tx = TransactionRequest {
+		from: from_account,
+		to: Some(address),
+		data: Some(function.encode_call(&params)?.into()),
+		..Default::default()
 	};
-
-	let tokens: Vec<Token> = Contract::from_json(WEB3.eth(), address, contract_abi.as_bytes())
-		.map_err(|e| Web3ContractError(e.into()))?
-		.query(&request.fn_name, params.as_slice(), from, Options::default(), None)
-		.await?;
-	let results = tokens.iter().map(|token| format!("{:?}", token)).collect();
-	Ok(results)
+	request.options.apply(&mut tx)?;
+	fill_gas(network, &mut tx).await.map_err(|e| e.decode_revert(abi))?;
+	let hash = submit(network, tx, signer.as_deref()).await.map_err(|e| e.decode_revert(abi))?;
+	network.tracker.track(
+		hash,
+		from_account,
+		TxKind::Call { contract_name },
+		request.confirmations,
+	);
+	Ok(hash)
 }
 
-/// ----------------------------------------
-/// ------------ private method ------------
-/// ----------------------------------------
-fn read_file(path: String) -> Result<String, anyhow::Error> {
-	let mut file = File::open(Path::new(&path))?;
-	let mut buf = String::new();
-	let _ = file.read_to_string(&mut buf);
-	Ok(buf)
+/// Sign the transaction with the signer, or let the node sign it.
+async fn submit(
+	network: &Network,
+	tx: TransactionRequest,
+	signer: Option<&dyn Signer>,
+) -> Result<H256> {
+	match signer {
+		Some(signer) => send_signed(network, tx, signer).await,
+		None => Ok(network.web3.eth().send_transaction(tx).await?),
+	}
 }
 
-fn parse_params(contract_abi: &String, request: &InvokeContractRequest) -> Result<Vec<Token>> {
-	let abi = contract_abi.parse::<ABI>()?;
-	let tokens = abi
-		.function_map
-		.get(&request.fn_name)
-		.ok_or(Error::InvalidParam("function not found in abi".to_string()))?
-		.to_params(&request.fn_params)?;
-	Ok(tokens)
+pub(crate) async fn query_sol_contract(
+	network: &Network,
+	request: InvokeContractRequest,
+) -> Result<JsonValue> {
+	let (contract_name, address) =
+		deployment::resolve(network, request.contract_name.as_deref(), &request.contract_address)?;
+	let artifact = registry::artifact(&contract_name)?;
+	let abi = &artifact.abi;
+	let (function, params) = abi.resolve(&request.fn_name, &request.fn_params)?;
+
+	let from = match request.from_account {
+		Some(from_account) => {
+			Some(from_account.parse::<H160>().map_err(|_| InvalidParam(from_account))?)
+		}
+		None => None,
+	};
+
+	let call = CallRequest {
+		from,
+		to: Some(address),
+		gas: request.options.gas,
+		value: request.options.value.map(Amount::wei),
+		data: Some(function.encode_call(&params)?.into()),
+		..Default::default()
+	};
+	let output =
+		network.web3.eth().call(call, None).await.map_err(|e| Error::from(e).decode_revert(abi))?;
+	let tokens =
+		decode(&function.output_types()?, &output.0).map_err(|e| Web3ContractError(e.into()))?;
+	Ok(function.outputs_to_json(tokens))
 }
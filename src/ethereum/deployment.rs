@@ -0,0 +1,436 @@
+use std::{fs, path::Path, sync::Mutex};
+
+use chrono::Utc;
+use log::info;
+use once_cell::sync::OnceCell;
+use rlp::RlpStream;
+use rusqlite::{params, Connection, ErrorCode, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::Value as JsonValue;
+use utoipa::ToSchema;
+use web3::{
+	signing::keccak256,
+	types::{H160, H256, U256},
+};
+
+use super::Network;
+use crate::{
+	contracts::to_checksum,
+	error::Error::{AnyError, Conflict, InvalidParam, NotFound},
+	Result,
+};
+
+static BOOK: OnceCell<DeploymentBook> = OnceCell::new();
+
+const SCHEMA: &str = "
+	CREATE TABLE IF NOT EXISTS deployments (
+		id INTEGER PRIMARY KEY AUTOINCREMENT,
+		network TEXT NOT NULL,
+		alias TEXT,
+		contract_name TEXT NOT NULL,
+		address TEXT NOT NULL,
+		deployer TEXT NOT NULL,
+		tx_hash TEXT NOT NULL,
+		block_number INTEGER,
+		constructor_args TEXT NOT NULL,
+		deployed_at TEXT NOT NULL,
+		status TEXT NOT NULL DEFAULT 'confirmed',
+		UNIQUE (network, address)
+	);
+	CREATE UNIQUE INDEX IF NOT EXISTS deployments_alias ON deployments (network, alias);
+";
+
+/// A contract instance deployed by this service.
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub(crate) struct Deployment {
+	id: i64,
+	network: String,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	alias: Option<String>,
+	pub contract_name: String,
+	pub address: String,
+	pub deployer: String,
+	/// Empty until the deployment is sent.
+	pub tx_hash: String,
+	block_number: Option<u64>,
+	constructor_args: JsonValue,
+	/// RFC 3339 time the deployment was sent or confirmed.
+	deployed_at: String,
+	pub status: DeploymentStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum DeploymentStatus {
+	/// Sent, the alias and address are reserved until it is confirmed or fails.
+	Pending,
+	Confirmed,
+}
+
+#[derive(Debug, Deserialize)]
+pub(crate) struct DeploymentQuery {
+	contract_name: Option<String>,
+}
+
+/// What the book learns of a deployment before it is sent.
+pub(crate) struct NewDeployment<'a> {
+	pub network: &'a str,
+	pub alias: Option<&'a str>,
+	pub contract_name: &'a str,
+	pub address: H160,
+	pub deployer: H160,
+	pub constructor_args: &'a JsonValue,
+}
+
+/// Deployments by network, address and alias, kept in a sqlite file.
+pub(crate) struct DeploymentBook {
+	conn: Mutex<Connection>,
+}
+
+impl DeploymentBook {
+	pub(crate) fn open(path: &Path) -> Result<DeploymentBook> {
+		if let Some(dir) = path.parent() {
+			fs::create_dir_all(dir).map_err(|e| AnyError(e.into()))?;
+		}
+		Self::with_connection(Connection::open(path).map_err(db_error)?)
+	}
+
+	fn with_connection(conn: Connection) -> Result<DeploymentBook> {
+		conn.execute_batch(SCHEMA).map_err(db_error)?;
+		// Books of earlier versions only hold confirmed deployments.
+		if conn.prepare("SELECT status FROM deployments").is_err() {
+			conn.execute_batch(
+				"ALTER TABLE deployments ADD COLUMN status TEXT NOT NULL DEFAULT 'confirmed'",
+			)
+			.map_err(db_error)?;
+		}
+		Ok(DeploymentBook { conn: Mutex::new(conn) })
+	}
+
+	/// Record a deployment as pending, which reserves its alias and address.
+	fn insert(&self, deployment: NewDeployment) -> Result<Deployment> {
+		let conn = self.conn.lock().unwrap();
+		conn.execute(
+			"INSERT INTO deployments (network, alias, contract_name, address, deployer, tx_hash,
+				constructor_args, deployed_at, status)
+			VALUES (?1, ?2, ?3, ?4, ?5, '', ?6, ?7, 'pending')",
+			params![
+				deployment.network,
+				deployment.alias,
+				deployment.contract_name,
+				to_checksum(&deployment.address),
+				to_checksum(&deployment.deployer),
+				deployment.constructor_args.to_string(),
+				Utc::now().to_rfc3339(),
+			],
+		)
+		.map_err(|e| match e.sqlite_error_code() {
+			Some(ErrorCode::ConstraintViolation) => Conflict(format!(
+				"deployment: {} is already recorded on {}",
+				deployment.alias.map_or_else(|| to_checksum(&deployment.address), str::to_string),
+				deployment.network
+			)),
+			_ => db_error(e),
+		})?;
+		let id = conn.last_insert_rowid();
+		conn.query_row("SELECT * FROM deployments WHERE id = ?1", [id], to_deployment)
+			.map_err(db_error)
+	}
+
+	/// Set the transaction of a pending deployment, or confirm it with its block.
+	fn update(
+		&self,
+		network: &str,
+		address: H160,
+		tx_hash: H256,
+		block_number: Option<u64>,
+	) -> Result<Deployment> {
+		let address = to_checksum(&address);
+		let status = if block_number.is_some() { "confirmed" } else { "pending" };
+		let conn = self.conn.lock().unwrap();
+		let updated = conn
+			.execute(
+				"UPDATE deployments SET tx_hash = ?3, block_number = ?4, status = ?5,
+					deployed_at = ?6
+				WHERE network = ?1 AND address = ?2",
+				params![
+					network,
+					address,
+					format!("{:?}", tx_hash),
+					block_number,
+					status,
+					Utc::now().to_rfc3339()
+				],
+			)
+			.map_err(db_error)?;
+		if updated == 0 {
+			return Err(NotFound(format!("deployment: {} on {}", address, network)));
+		}
+		conn.query_row(
+			"SELECT * FROM deployments WHERE network = ?1 AND address = ?2",
+			[network, &address],
+			to_deployment,
+		)
+		.map_err(db_error)
+	}
+
+	/// Drop a pending deployment which won't happen, freeing its alias.
+	fn discard(&self, network: &str, address: H160) -> Result<()> {
+		self.conn
+			.lock()
+			.unwrap()
+			.execute(
+				"DELETE FROM deployments WHERE network = ?1 AND address = ?2 AND status = 'pending'",
+				[network, &to_checksum(&address)],
+			)
+			.map_err(db_error)?;
+		Ok(())
+	}
+
+	fn pending(&self, network: &str) -> Result<Vec<Deployment>> {
+		let conn = self.conn.lock().unwrap();
+		let mut stmt = conn
+			.prepare("SELECT * FROM deployments WHERE network = ?1 AND status = 'pending'")
+			.map_err(db_error)?;
+		let rows = stmt.query_map([network], to_deployment).map_err(db_error)?;
+		rows.collect::<rusqlite::Result<_>>().map_err(db_error)
+	}
+
+	/// A deployment by its address or alias.
+	fn find(&self, network: &str, id: &str) -> Result<Option<Deployment>> {
+		let id = match id.parse::<H160>() {
+			Ok(address) => to_checksum(&address),
+			Err(_) => id.to_string(),
+		};
+		self.conn
+			.lock()
+			.unwrap()
+			.query_row(
+				"SELECT * FROM deployments WHERE network = ?1 AND (address = ?2 OR alias = ?2)",
+				[network, &id],
+				to_deployment,
+			)
+			.optional()
+			.map_err(db_error)
+	}
+
+	fn list(&self, network: &str, contract_name: Option<&str>) -> Result<Vec<Deployment>> {
+		let conn = self.conn.lock().unwrap();
+		let mut stmt = conn
+			.prepare(
+				"SELECT * FROM deployments WHERE network = ?1
+					AND (?2 IS NULL OR contract_name = ?2) ORDER BY id",
+			)
+			.map_err(db_error)?;
+		let rows =
+			stmt.query_map(params![network, contract_name], to_deployment).map_err(db_error)?;
+		rows.collect::<rusqlite::Result<_>>().map_err(db_error)
+	}
+}
+
+/// Open the deployment book, must be called once before the other functions.
+pub(crate) fn init(path: &Path) -> Result<()> {
+	let book = DeploymentBook::open(path)?;
+	info!("Deployments are recorded in {}", path.display());
+	let _ = BOOK.set(book);
+	Ok(())
+}
+
+fn book() -> &'static DeploymentBook {
+	BOOK.get().expect("deployment book is not initialized")
+}
+
+/// Reserve the alias and address of a deployment before it is sent.
+pub(crate) fn record(deployment: NewDeployment) -> Result<Deployment> {
+	book().insert(deployment)
+}
+
+pub(crate) fn sent(network: &Network, address: H160, tx_hash: H256) -> Result<Deployment> {
+	book().update(&network.name, address, tx_hash, None)
+}
+
+pub(crate) fn confirm(
+	network: &Network,
+	address: H160,
+	tx_hash: H256,
+	block_number: u64,
+) -> Result<Deployment> {
+	book().update(&network.name, address, tx_hash, Some(block_number))
+}
+
+pub(crate) fn discard(network: &Network, address: H160) -> Result<()> {
+	book().discard(&network.name, address)
+}
+
+/// Deployments which were sent but not confirmed before a restart.
+pub(crate) fn pending(network: &Network) -> Result<Vec<Deployment>> {
+	book().pending(&network.name)
+}
+
+/// Where a contract created by `deployer` with `nonce` ends up.
+pub(crate) fn contract_address(deployer: H160, nonce: U256) -> H160 {
+	let mut stream = RlpStream::new_list(2);
+	stream.append(&deployer).append(&nonce);
+	H160::from_slice(&keccak256(&stream.out())[12..])
+}
+
+pub(crate) fn list(network: &Network, query: &DeploymentQuery) -> Result<Vec<Deployment>> {
+	book().list(&network.name, query.contract_name.as_deref())
+}
+
+pub(crate) fn lookup(network: &Network, id: &str) -> Result<Deployment> {
+	book()
+		.find(&network.name, id)?
+		.ok_or_else(|| NotFound(format!("deployment: {} on {}", id, network.name)))
+}
+
+/// Fail early if an alias is taken, before a deployment costs gas.
+pub(crate) fn check_alias(network: &Network, alias: &str) -> Result<()> {
+	let valid = !alias.starts_with("0x")
+		&& !alias.is_empty()
+		&& alias.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+	if !valid {
+		return Err(InvalidParam(format!("alias: {} is invalid", alias)));
+	}
+	match book().find(&network.name, alias)? {
+		Some(_) => Err(Conflict(format!("alias: {} is taken on {}", alias, network.name))),
+		None => Ok(()),
+	}
+}
+
+/// The contract name and address of a call, the address may be an alias and the name may be left
+/// out for recorded deployments.
+pub(crate) fn resolve(
+	network: &Network,
+	contract_name: Option<&str>,
+	contract_address: &str,
+) -> Result<(String, H160)> {
+	let recorded = book().find(&network.name, contract_address)?;
+	match (contract_name, recorded) {
+		(Some(name), Some(deployment)) if name != deployment.contract_name => {
+			Err(InvalidParam(format!(
+				"{} is a {} deployment, not {}",
+				contract_address, deployment.contract_name, name
+			)))
+		}
+		(_, Some(deployment)) => {
+			Ok((deployment.contract_name, deployment.address.parse().unwrap()))
+		}
+		(Some(name), None) => {
+			let address = contract_address
+				.parse()
+				.map_err(|_| NotFound(format!("deployment: {}", contract_address)))?;
+			Ok((name.to_string(), address))
+		}
+		(None, None) => Err(InvalidParam(format!(
+			"contract_name is required, {} is no recorded deployment",
+			contract_address
+		))),
+	}
+}
+
+fn to_deployment(row: &Row) -> rusqlite::Result<Deployment> {
+	let args: String = row.get("constructor_args")?;
+	let status: String = row.get("status")?;
+	Ok(Deployment {
+		id: row.get("id")?,
+		network: row.get("network")?,
+		alias: row.get("alias")?,
+		contract_name: row.get("contract_name")?,
+		address: row.get("address")?,
+		deployer: row.get("deployer")?,
+		tx_hash: row.get("tx_hash")?,
+		block_number: row.get("block_number")?,
+		constructor_args: serde_json::from_str(&args).unwrap_or_default(),
+		deployed_at: row.get("deployed_at")?,
+		status: match status.as_str() {
+			"pending" => DeploymentStatus::Pending,
+			_ => DeploymentStatus::Confirmed,
+		},
+	})
+}
+
+fn db_error(err: rusqlite::Error) -> crate::error::Error {
+	AnyError(anyhow::anyhow!("deployment book: {}", err))
+}
+
+#[cfg(test)]
+mod tests {
+	use rusqlite::Connection;
+	use serde_json::json;
+	use web3::types::H160;
+
+	use super::{contract_address, DeploymentBook, DeploymentStatus, NewDeployment};
+
+	#[test]
+	fn test_deployment_book() {
+		let book = DeploymentBook::with_connection(Connection::open_in_memory().unwrap()).unwrap();
+		let address: H160 = "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed".parse().unwrap();
+		let args = json!([42]);
+		let new = |alias| NewDeployment {
+			network: "local",
+			alias,
+			contract_name: "ValueStorage",
+			address,
+			deployer: H160::zero(),
+			constructor_args: &args,
+		};
+
+		let deployment = book.insert(new(Some("storage"))).unwrap();
+		assert_eq!(deployment.address, "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed");
+		assert_eq!(deployment.constructor_args, args);
+		assert_eq!(deployment.status, DeploymentStatus::Pending);
+		assert!(book.insert(new(None)).is_err());
+		assert_eq!(book.pending("local").unwrap().len(), 1);
+
+		book.update("local", address, Default::default(), None).unwrap();
+		let deployment = book.update("local", address, Default::default(), Some(7)).unwrap();
+		assert_eq!(
+			(deployment.status, deployment.block_number),
+			(DeploymentStatus::Confirmed, Some(7))
+		);
+		assert!(book.pending("local").unwrap().is_empty());
+		// Confirmed deployments stay.
+		book.discard("local", address).unwrap();
+
+		let found = book.find("local", "storage").unwrap().unwrap();
+		assert_eq!(found.id, deployment.id);
+		let found = book.find("local", "0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed").unwrap();
+		assert_eq!(found.unwrap().alias.as_deref(), Some("storage"));
+		assert!(book.find("goerli", "storage").unwrap().is_none());
+
+		assert_eq!(book.list("local", Some("ValueStorage")).unwrap().len(), 1);
+		assert!(book.list("local", Some("Auction")).unwrap().is_empty());
+
+		let other = new(Some("other"));
+		let other = book.insert(NewDeployment { address: H160::repeat_byte(1), ..other }).unwrap();
+		assert!(book.find("local", "other").unwrap().is_some());
+		book.discard("local", other.address.parse().unwrap()).unwrap();
+		assert!(book.find("local", "other").unwrap().is_none());
+	}
+
+	#[test]
+	fn test_upgrade_book() {
+		let conn = Connection::open_in_memory().unwrap();
+		conn.execute_batch(
+			"CREATE TABLE deployments (id INTEGER PRIMARY KEY AUTOINCREMENT, network TEXT NOT NULL,
+				alias TEXT, contract_name TEXT NOT NULL, address TEXT NOT NULL,
+				deployer TEXT NOT NULL, tx_hash TEXT NOT NULL, block_number INTEGER,
+				constructor_args TEXT NOT NULL, deployed_at TEXT NOT NULL);
+			INSERT INTO deployments VALUES (1, 'local', NULL, 'ValueStorage', '0x00', '0x00',
+				'0x00', 7, '[]', '2022-10-01T00:00:00+00:00');",
+		)
+		.unwrap();
+		let book = DeploymentBook::with_connection(conn).unwrap();
+		assert_eq!(book.list("local", None).unwrap()[0].status, DeploymentStatus::Confirmed);
+	}
+
+	#[test]
+	fn test_contract_address() {
+		let deployer: H160 = "0x6ac7ea33f8831ea9dcc53393aaa88b25a785dbf0".parse().unwrap();
+		let address: H160 = "0xcd234a471b72ba2f1ccf0a70fcaba648a5eecd8d".parse().unwrap();
+		assert_eq!(contract_address(deployer, 0.into()), address);
+		let address: H160 = "0x343c43a37d37dff08ae8c4a11544c718abb4fcf8".parse().unwrap();
+		assert_eq!(contract_address(deployer, 1.into()), address);
+	}
+}
@@ -0,0 +1,333 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value as JsonValue};
+use utoipa::ToSchema;
+use web3::{
+	ethabi::{encode, param_type::Reader, Token},
+	signing::{hash_message, keccak256, recover, Key, SecretKeyRef},
+	types::{Bytes, H160, H256},
+};
+
+use super::transaction::sender;
+use crate::{
+	contracts::json_to_token,
+	error::Error::{InvalidParam, NotFound},
+	Result,
+};
+
+const DOMAIN_TYPE: &str = "EIP712Domain";
+/// Members of the domain in the order of EIP-712, used when the types leave it out.
+const DOMAIN_FIELDS: [(&str, &str); 5] = [
+	("name", "string"),
+	("version", "string"),
+	("chainId", "uint256"),
+	("verifyingContract", "address"),
+	("salt", "bytes32"),
+];
+
+/// EIP-712 typed data as in `eth_signTypedData_v4`.
+#[derive(Debug, Deserialize, ToSchema)]
+#[serde(rename_all = "camelCase")]
+pub(crate) struct TypedData {
+	/// Struct types by name, `EIP712Domain` is derived from the domain when left out.
+	#[schema(value_type = Object)]
+	types: BTreeMap<String, Vec<TypedMember>>,
+	primary_type: String,
+	#[schema(value_type = Object)]
+	domain: Map<String, JsonValue>,
+	#[schema(value_type = Object)]
+	message: JsonValue,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct TypedMember {
+	name: String,
+	r#type: String,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub(crate) struct SignMessageRequest {
+	/// Text to sign, or bytes given as `0x` hex.
+	message: Option<String>,
+	/// Typed data to sign instead of a message.
+	typed_data: Option<TypedData>,
+	/// Signer account, a signer or a wallet account.
+	from: Option<String>,
+	/// Alias or address of the keystore signer.
+	signer: Option<String>,
+	secret_key: Option<String>,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub(crate) struct VerifyRequest {
+	message: Option<String>,
+	typed_data: Option<TypedData>,
+	/// 65 bytes `r`, `s`, `v` as `0x` hex, `v` is 27/28 or 0/1.
+	#[schema(value_type = String)]
+	signature: Bytes,
+	/// Expected signer, `valid` tells whether it signed.
+	#[schema(value_type = String)]
+	address: Option<H160>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct MessageSignature {
+	#[schema(value_type = String)]
+	signature: Bytes,
+	/// The signed digest.
+	#[schema(value_type = String)]
+	hash: H256,
+	#[schema(value_type = String)]
+	address: H160,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct Verification {
+	/// Recovered signer.
+	#[schema(value_type = String)]
+	address: H160,
+	#[schema(value_type = String)]
+	hash: H256,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	valid: Option<bool>,
+}
+
+impl TypedData {
+	/// The EIP-712 digest `keccak256("\x19\x01" ‖ domainSeparator ‖ hashStruct(message))`.
+	fn hash(&self) -> Result<H256> {
+		let mut types = self.types.clone();
+		types.entry(DOMAIN_TYPE.to_string()).or_insert_with(|| {
+			DOMAIN_FIELDS
+				.iter()
+				.filter(|(name, _)| self.domain.contains_key(*name))
+				.map(|(name, r#type)| TypedMember {
+					name: name.to_string(),
+					r#type: r#type.to_string(),
+				})
+				.collect()
+		});
+
+		let domain = JsonValue::Object(self.domain.clone());
+		let mut encoded = vec![0x19, 0x01];
+		encoded.extend(hash_struct(&types, DOMAIN_TYPE, &domain)?);
+		if self.primary_type != DOMAIN_TYPE {
+			encoded.extend(hash_struct(&types, &self.primary_type, &self.message)?);
+		}
+		Ok(keccak256(&encoded).into())
+	}
+}
+
+/// `typeHash ‖ encodeData` of a struct, hashed.
+fn hash_struct(
+	types: &BTreeMap<String, Vec<TypedMember>>,
+	name: &str,
+	value: &JsonValue,
+) -> Result<[u8; 32]> {
+	let members = types.get(name).ok_or_else(|| NotFound(format!("type: {}", name)))?;
+	let fields = value
+		.as_object()
+		.ok_or_else(|| InvalidParam(format!("{} is not a valid {}", value, name)))?;
+
+	let mut encoded = keccak256(encode_type(types, name)?.as_bytes()).to_vec();
+	for member in members {
+		let field = fields.get(&member.name).unwrap_or(&JsonValue::Null);
+		let value = encode_value(types, &member.r#type, field)
+			.map_err(|e| InvalidParam(format!("{}.{}: {}", name, member.name, e)))?;
+		encoded.extend(value);
+	}
+	Ok(keccak256(&encoded))
+}
+
+/// `Name(type member,..)` followed by the referenced struct types sorted by name.
+fn encode_type(types: &BTreeMap<String, Vec<TypedMember>>, name: &str) -> Result<String> {
+	let mut referenced = BTreeSet::new();
+	let mut pending = vec![name];
+	while let Some(name) = pending.pop() {
+		let members = types.get(name).ok_or_else(|| NotFound(format!("type: {}", name)))?;
+		for member in members {
+			let base = member.r#type.split('[').next().unwrap_or_default();
+			if types.contains_key(base) && base != name && referenced.insert(base) {
+				pending.push(base);
+			}
+		}
+	}
+	referenced.remove(name);
+
+	let describe = |name: &str| {
+		let members = types[name].iter().map(|member| format!("{} {}", member.r#type, member.name));
+		format!("{}({})", name, members.collect::<Vec<_>>().join(","))
+	};
+	Ok(std::iter::once(name).chain(referenced).map(describe).collect())
+}
+
+fn encode_value(
+	types: &BTreeMap<String, Vec<TypedMember>>,
+	r#type: &str,
+	value: &JsonValue,
+) -> Result<[u8; 32]> {
+	if let Some((inner, len)) = r#type.strip_suffix(']').and_then(|s| s.rsplit_once('[')) {
+		let items = value
+			.as_array()
+			.filter(|items| len.is_empty() || len.parse() == Ok(items.len()))
+			.ok_or_else(|| InvalidParam(format!("{} is not a valid {}", value, r#type)))?;
+		let mut encoded = Vec::with_capacity(items.len() * 32);
+		for item in items {
+			encoded.extend(encode_value(types, inner, item)?);
+		}
+		return Ok(keccak256(&encoded));
+	}
+	if types.contains_key(r#type) {
+		return hash_struct(types, r#type, value);
+	}
+
+	let kind = Reader::read(r#type)
+		.map_err(|e| InvalidParam(format!("type: {} is invalid, {}", r#type, e)))?;
+	let token = json_to_token(value, &kind, None)?;
+	match token {
+		Token::String(s) => Ok(keccak256(s.as_bytes())),
+		Token::Bytes(bytes) => Ok(keccak256(&bytes)),
+		// Only these take exactly one word, static tuples and fixed arrays of them take more.
+		token @ (Token::Address(_)
+		| Token::Bool(_)
+		| Token::Int(_)
+		| Token::Uint(_)
+		| Token::FixedBytes(_)) => {
+			let mut word = [0u8; 32];
+			word.copy_from_slice(&encode(&[token]));
+			Ok(word)
+		}
+		_ => Err(InvalidParam(format!("type: {} is not supported", r#type))),
+	}
+}
+
+/// The digest of a message or typed data, exactly one of which must be given.
+fn digest(message: Option<&str>, typed_data: Option<&TypedData>) -> Result<H256> {
+	match (message, typed_data) {
+		(Some(message), None) => {
+			let bytes = match message.strip_prefix("0x").map(hex::decode) {
+				Some(Ok(bytes)) => bytes,
+				_ => message.as_bytes().to_vec(),
+			};
+			Ok(hash_message(bytes))
+		}
+		(None, Some(typed_data)) => typed_data.hash(),
+		_ => Err(InvalidParam("either message or typed_data is required".to_string())),
+	}
+}
+
+/// Sign a message the way `personal_sign` does (EIP-191) or typed data (EIP-712).
+pub(crate) fn sign(request: SignMessageRequest) -> Result<MessageSignature> {
+	let (address, signer) =
+		sender(request.from.as_deref(), request.signer.as_deref(), request.secret_key.as_deref())?;
+	let key = signer
+		.as_ref()
+		.and_then(|signer| signer.secret_key())
+		.ok_or_else(|| InvalidParam(format!("no local key of {:?}", address)))?;
+	let hash = digest(request.message.as_deref(), request.typed_data.as_ref())?;
+
+	let signature = SecretKeyRef::new(key)
+		.sign(hash.as_bytes(), None)
+		.map_err(|e| InvalidParam(format!("sign failed, {:?}", e)))?;
+	let mut bytes = [signature.r.as_bytes(), signature.s.as_bytes()].concat();
+	bytes.push(signature.v as u8);
+	Ok(MessageSignature { signature: bytes.into(), hash, address })
+}
+
+/// Recover the signer of a message or typed data, and compare it to the expected one.
+pub(crate) fn verify(request: VerifyRequest) -> Result<Verification> {
+	let hash = digest(request.message.as_deref(), request.typed_data.as_ref())?;
+	let signature = &request.signature.0;
+	let recovery_id = match signature.get(64) {
+		Some(v @ (27 | 28)) if signature.len() == 65 => v - 27,
+		Some(v @ (0 | 1)) if signature.len() == 65 => *v,
+		_ => {
+			return Err(InvalidParam(
+				"signature must be 65 bytes with v of 27/28 or 0/1".to_string(),
+			))
+		}
+	};
+	let address = recover(hash.as_bytes(), &signature[..64], recovery_id as i32)
+		.map_err(|_| InvalidParam("signature doesn't recover a signer".to_string()))?;
+	let valid = request.address.map(|expected| expected == address);
+	Ok(Verification { address, hash, valid })
+}
+
+#[cfg(test)]
+mod tests {
+	use serde_json::{json, Value as JsonValue};
+	use web3::types::{H160, H256};
+
+	use super::{digest, verify, TypedData, VerifyRequest};
+	use crate::error::Error::InvalidParam;
+
+	#[test]
+	fn test_typed_data_signature() {
+		// The example of EIP-712, signed by keccak256("cow").
+		let typed_data: TypedData = serde_json::from_value(json!({
+			"types": {
+				"EIP712Domain": [
+					{"name": "name", "type": "string"},
+					{"name": "version", "type": "string"},
+					{"name": "chainId", "type": "uint256"},
+					{"name": "verifyingContract", "type": "address"}
+				],
+				"Person": [{"name": "name", "type": "string"}, {"name": "wallet", "type": "address"}],
+				"Mail": [
+					{"name": "from", "type": "Person"},
+					{"name": "to", "type": "Person"},
+					{"name": "contents", "type": "string"}
+				]
+			},
+			"primaryType": "Mail",
+			"domain": {
+				"name": "Ether Mail",
+				"version": "1",
+				"chainId": 1,
+				"verifyingContract": "0xCcCCccccCCCCcCCCCCCcCcCccCcCCCcCcccccccC"
+			},
+			"message": {
+				"from": {"name": "Cow", "wallet": "0xCD2a3d9F938E13CD947Ec05AbC7FE734Df8DD826"},
+				"to": {"name": "Bob", "wallet": "0xbBbBBBBbbBBBbbbBbbBbbbbBBbBbbbbBbBbbBBbB"},
+				"contents": "Hello, Bob!"
+			}
+		}))
+		.unwrap();
+		let hash = "0xbe609aee343fb3c4b28e1df9e632fca64fcfaede20f02e86244efddf30957bd2";
+		assert_eq!(typed_data.hash().unwrap(), hash.parse::<H256>().unwrap());
+
+		let signature = "4355c47d63924e8a72e509b65029052eb6c299d53a04e167c5775fd466751c9d\
+			07299936d304c153f6443dfa05f40ff007d72911b6f72307f996231605b915621c";
+		let cow = "0xcd2a3d9f938e13cd947ec05abc7fe734df8dd826".parse::<H160>().unwrap();
+		let verification = verify(VerifyRequest {
+			message: None,
+			typed_data: Some(typed_data),
+			signature: hex::decode(signature).unwrap().into(),
+			address: Some(cow),
+		})
+		.unwrap();
+		assert_eq!(verification.valid, Some(true));
+
+		assert_eq!(digest(Some("0x68656c6c6f"), None).ok(), digest(Some("hello"), None).ok());
+		assert!(digest(None, None).is_err());
+	}
+
+	#[test]
+	fn test_unsupported_type() {
+		let typed_data = |r#type: &str, value: JsonValue| -> TypedData {
+			serde_json::from_value(json!({
+				"types": {
+					"EIP712Domain": [{"name": "name", "type": "string"}],
+					"Pair": [{"name": "value", "type": r#type}]
+				},
+				"primaryType": "Pair",
+				"domain": {"name": "pair"},
+				"message": {"value": value}
+			}))
+			.unwrap()
+		};
+		assert!(typed_data("uint256", json!(1)).hash().is_ok());
+		let err = typed_data("(uint256,uint256)", json!([1, 2])).hash().unwrap_err();
+		assert!(matches!(err, InvalidParam(_)), "{}", err);
+	}
+}
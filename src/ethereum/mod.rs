@@ -1,13 +1,145 @@
-use once_cell::sync::Lazy;
-use web3::{transports::Http, Web3};
+use std::collections::HashMap;
+
+use log::info;
+use once_cell::sync::OnceCell;
+use web3::{types::U256, Web3};
+
+use self::{
+	transaction::{
+		nonce::NonceManager,
+		tracker::{self, Tracker},
+	},
+	transport::NodeTransport,
+};
+use crate::{
+	config,
+	error::Error::{ConfigError, NotFound},
+	Result,
+};
 
 pub(crate) mod account;
+pub(crate) mod amount;
 pub(crate) mod contract;
+pub(crate) mod deployment;
+pub(crate) mod message;
+pub(crate) mod pool;
+pub(crate) mod signer;
 pub(crate) mod transaction;
+pub(crate) mod transport;
+pub(crate) mod wallet;
+
+static NETWORKS: OnceCell<HashMap<String, Network>> = OnceCell::new();
+
+/// A named chain with its own node client.
+#[derive(Debug)]
+pub(crate) struct Network {
+	pub name: String,
+	pub chain_id: U256,
+	/// Padding of estimated gas limits.
+	pub gas_multiplier: f64,
+	pub web3: Web3<NodeTransport>,
+	/// Nonces of the accounts which sign locally, and of the ones deploying contracts.
+	pub nonces: NonceManager,
+	pub tracker: Tracker,
+}
+
+/// Connect to the node of every configured network and make sure it serves the expected chain.
+pub(crate) async fn init() -> Result<()> {
+	let mut networks = HashMap::new();
+	for (name, config) in &config::get().networks {
+		let web3 = Web3::new(NodeTransport::new(config)?);
+		let chain_id = web3.eth().chain_id().await?;
+		if let Some(expected) = config.chain_id.filter(|expected| chain_id != (*expected).into()) {
+			return Err(ConfigError(format!(
+				"chain id mismatch of network {}, expected: {}, node: {}",
+				name, expected, chain_id
+			)));
+		}
+
+		info!("Network {} connected to {}, chain id: {}", name, config.rpc_url, chain_id);
+		let gas_multiplier = config.gas_multiplier;
+		networks.insert(
+			name.clone(),
+			Network {
+				name: name.clone(),
+				chain_id,
+				gas_multiplier,
+				web3,
+				nonces: NonceManager::default(),
+				tracker: Tracker::default(),
+			},
+		);
+	}
+
+	NETWORKS
+		.set(networks)
+		.map_err(|_| ConfigError("networks are initialized twice".to_string()))?;
+	for network in NETWORKS.get().into_iter().flat_map(HashMap::values) {
+		tokio::spawn(tracker::run(network));
+	}
+	Ok(())
+}
+
+/// Look up a network by name, `None` means the default network.
+pub(crate) fn network(name: Option<&str>) -> Result<&'static Network> {
+	let name = name.unwrap_or(&config::get().default_network);
+	NETWORKS
+		.get()
+		.expect("networks are not initialized")
+		.get(name)
+		.ok_or_else(|| NotFound(format!("network: {}", name)))
+}
+
+/// A network and a stand-in node for the tests.
+#[cfg(test)]
+pub(crate) mod testing {
+	use std::{future::Future, sync::Arc};
+
+	use axum::{http::StatusCode, routing::post, Extension, Json, Router};
+	use futures::{future::BoxFuture, FutureExt};
+	use serde_json::{json, Value};
+	use web3::Web3;
+
+	use super::{transport::NodeTransport, Network};
+	use crate::config::NetworkConfig;
+
+	type Handler =
+		Arc<dyn Fn(Value) -> BoxFuture<'static, Result<Value, StatusCode>> + Send + Sync>;
+
+	/// A network on chain 1337 with the node at `rpc_url`.
+	pub(crate) fn network(rpc_url: &str) -> Network {
+		let config = NetworkConfig { rpc_url: rpc_url.to_string(), ..Default::default() };
+		Network {
+			name: "local".to_string(),
+			chain_id: 1337.into(),
+			gas_multiplier: 1.0,
+			web3: Web3::new(NodeTransport::new(&config).unwrap()),
+			nonces: Default::default(),
+			tracker: Default::default(),
+		}
+	}
 
-pub(crate) const WEB3_URL: &str = "http://localhost:8545";
+	/// Serve a stand-in node, `handler` answers the result of each JSON-RPC request or the
+	/// http status of a failing node. Returns the url of the node.
+	pub(crate) async fn serve<F, Fut>(handler: F) -> String
+	where
+		F: Fn(Value) -> Fut + Send + Sync + 'static,
+		Fut: Future<Output = Result<Value, StatusCode>> + Send + 'static,
+	{
+		async fn handle(
+			Extension(handler): Extension<Handler>,
+			Json(request): Json<Value>,
+		) -> Result<Json<Value>, StatusCode> {
+			let result = handler(request.clone()).await?;
+			Ok(Json(json!({"jsonrpc": "2.0", "id": request["id"], "result": result})))
+		}
 
-static WEB3: Lazy<Web3<Http>> = Lazy::new(|| {
-	let http = Http::new(WEB3_URL).unwrap();
-	Web3::new(http)
-});
+		let handler: Handler = Arc::new(move |request| handler(request).boxed());
+		let app = Router::new().route("/", post(handle)).layer(Extension(handler));
+		let server =
+			axum::Server::bind(&"127.0.0.1:0".parse().unwrap()).serve(app.into_make_service());
+		let url = format!("http://{}", server.local_addr());
+		tokio::spawn(server);
+		url
+	}
+}
@@ -0,0 +1,318 @@
+use std::{
+	sync::{
+		atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering},
+		Arc,
+	},
+	time::Duration,
+};
+
+use futures::future::join_all;
+use jsonrpc_core::Call;
+use log::{info, warn};
+use serde_json::Value;
+use web3::{
+	error::{Error as Web3Error, TransportError},
+	helpers, RequestId, Transport,
+};
+
+use super::transport::{is_idempotent, NodeTransport};
+use crate::config::NetworkConfig;
+
+/// Ignore the error rate of upstreams which served fewer requests since the last check.
+const MIN_SAMPLES: u64 = 5;
+
+/// Several endpoints of one chain, reads are spread round-robin over the healthy ones while
+/// writes stick to the first healthy one so that nonces and the mempool stay consistent.
+#[derive(Debug)]
+pub(crate) struct Pool {
+	upstreams: Vec<Upstream>,
+	next: AtomicUsize,
+	max_block_lag: u64,
+	max_error_rate: f64,
+}
+
+#[derive(Debug)]
+struct Upstream {
+	url: String,
+	transport: NodeTransport,
+	healthy: AtomicBool,
+	requests: AtomicU64,
+	errors: AtomicU64,
+}
+
+impl Pool {
+	pub(crate) fn new(
+		upstreams: Vec<(String, NodeTransport)>,
+		config: &NetworkConfig,
+	) -> Arc<Self> {
+		let pool = Arc::new(Pool::build(upstreams, config));
+		tokio::spawn(pool.clone().health_check(config.health_check_interval()));
+		pool
+	}
+
+	fn build(upstreams: Vec<(String, NodeTransport)>, config: &NetworkConfig) -> Self {
+		let upstreams = upstreams
+			.into_iter()
+			.map(|(url, transport)| Upstream {
+				url,
+				transport,
+				healthy: AtomicBool::new(true),
+				requests: AtomicU64::new(0),
+				errors: AtomicU64::new(0),
+			})
+			.collect();
+		Pool {
+			upstreams,
+			next: AtomicUsize::new(0),
+			max_block_lag: config.max_block_lag,
+			max_error_rate: config.max_error_rate,
+		}
+	}
+
+	pub(crate) async fn send(self: Arc<Self>, id: RequestId, call: Call) -> web3::Result<Value> {
+		let method = match &call {
+			Call::MethodCall(method_call) => method_call.method.clone(),
+			_ => String::new(),
+		};
+
+		let candidates = self.candidates(is_pinned(&method));
+		let mut last_err = None;
+		for upstream in candidates {
+			upstream.requests.fetch_add(1, Ordering::Relaxed);
+			match upstream.transport.send(id, call.clone()).await {
+				Err(err) if is_node_failure(&err) => {
+					upstream.errors.fetch_add(1, Ordering::Relaxed);
+					warn!("Upstream {} failed on {}: {}", upstream.url, method, err);
+					if !is_idempotent(&method) {
+						return Err(err);
+					}
+					last_err = Some(err);
+				}
+				result => return result,
+			}
+		}
+
+		Err(last_err.unwrap_or_else(|| {
+			Web3Error::Transport(TransportError::Message("no upstream available".to_string()))
+		}))
+	}
+
+	/// The transport that subscriptions and pinned requests go to.
+	pub(crate) fn primary(&self) -> &NodeTransport {
+		&self.candidates(true)[0].transport
+	}
+
+	/// Upstreams in the order they should be tried, the unhealthy ones come last.
+	fn candidates(&self, pinned: bool) -> Vec<&Upstream> {
+		let start = if pinned { 0 } else { self.next.fetch_add(1, Ordering::Relaxed) };
+		let len = self.upstreams.len();
+		let mut ordered: Vec<&Upstream> =
+			(0..len).map(|idx| &self.upstreams[(start + idx) % len]).collect();
+		ordered.sort_by_key(|upstream| !upstream.healthy.load(Ordering::Relaxed));
+		ordered
+	}
+
+	async fn health_check(self: Arc<Self>, interval: Duration) {
+		let mut ticker = tokio::time::interval(interval);
+		loop {
+			ticker.tick().await;
+			self.check().await;
+		}
+	}
+
+	/// Mark the upstreams lagging behind the best block or failing too often as unhealthy.
+	async fn check(&self) {
+		let heights = join_all(self.upstreams.iter().map(|upstream| async move {
+			let (id, call) = upstream.transport.prepare("eth_blockNumber", vec![]);
+			let height = upstream.transport.send(id, call).await;
+			height.and_then(helpers::decode::<web3::types::U64>).map(|h| h.as_u64())
+		}))
+		.await;
+
+		let best = heights.iter().filter_map(|h| h.as_ref().ok()).max().copied().unwrap_or(0);
+		for (upstream, height) in self.upstreams.iter().zip(heights) {
+			let requests = upstream.requests.swap(0, Ordering::Relaxed);
+			let errors = upstream.errors.swap(0, Ordering::Relaxed);
+			let error_rate =
+				if requests < MIN_SAMPLES { 0.0 } else { errors as f64 / requests as f64 };
+
+			let healthy = match &height {
+				Ok(height) => {
+					best - height <= self.max_block_lag && error_rate <= self.max_error_rate
+				}
+				Err(_) => false,
+			};
+			if upstream.healthy.swap(healthy, Ordering::Relaxed) != healthy {
+				info!(
+					"Upstream {} is {}, block: {:?}, best: {}, error rate: {:.2}",
+					upstream.url,
+					if healthy { "healthy" } else { "unhealthy" },
+					height,
+					best,
+					error_rate
+				);
+			}
+		}
+	}
+}
+
+/// Requests that depend on the node's mempool or managed accounts.
+fn is_pinned(method: &str) -> bool {
+	matches!(
+		method,
+		"eth_sendTransaction"
+			| "eth_sendRawTransaction"
+			| "eth_getTransactionCount"
+			| "eth_accounts"
+			| "eth_sign"
+			| "eth_signTransaction"
+			| "eth_subscribe"
+			| "eth_unsubscribe"
+	) || method.starts_with("personal_")
+		|| method.starts_with("txpool_")
+}
+
+/// Errors worth another upstream, rpc errors are answers of a working node.
+fn is_node_failure(err: &Web3Error) -> bool {
+	!matches!(err, Web3Error::Rpc(_))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::{
+		atomic::{AtomicBool, AtomicU64, Ordering},
+		Arc,
+	};
+
+	use axum::http::StatusCode;
+	use serde_json::{json, Value};
+	use web3::Transport;
+
+	use super::{is_pinned, Pool, MIN_SAMPLES};
+	use crate::{
+		config::NetworkConfig,
+		ethereum::{testing, transport::NodeTransport},
+	};
+
+	/// A stand-in node counting the requests it served, it answers 500 while failing.
+	#[derive(Default)]
+	struct FakeNode {
+		block: AtomicU64,
+		failing: AtomicBool,
+		hits: AtomicU64,
+	}
+
+	async fn serve(node: Arc<FakeNode>) -> String {
+		testing::serve(move |request: Value| {
+			let node = node.clone();
+			async move {
+				if node.failing.load(Ordering::Relaxed) {
+					return Err(StatusCode::INTERNAL_SERVER_ERROR);
+				}
+				Ok(match request["method"].as_str() {
+					Some("eth_blockNumber") => {
+						json!(format!("{:#x}", node.block.load(Ordering::Relaxed)))
+					}
+					_ => {
+						node.hits.fetch_add(1, Ordering::Relaxed);
+						json!("0x1")
+					}
+				})
+			}
+		})
+		.await
+	}
+
+	async fn pool(nodes: &[Arc<FakeNode>]) -> Pool {
+		let mut upstreams = vec![];
+		for node in nodes {
+			let rpc_url = serve(node.clone()).await;
+			let config = NetworkConfig { rpc_url: rpc_url.clone(), ..Default::default() };
+			upstreams.push((rpc_url, NodeTransport::new(&config).unwrap()));
+		}
+		Pool::build(upstreams, &NetworkConfig::default())
+	}
+
+	async fn send(pool: &Arc<Pool>, method: &str) -> web3::Result<Value> {
+		let (id, call) = pool.primary().prepare(method, vec![]);
+		pool.clone().send(id, call).await
+	}
+
+	fn hits(nodes: &[Arc<FakeNode>]) -> Vec<u64> {
+		nodes.iter().map(|node| node.hits.load(Ordering::Relaxed)).collect()
+	}
+
+	#[tokio::test]
+	async fn test_reads_and_writes() {
+		let nodes = [Arc::new(FakeNode::default()), Arc::new(FakeNode::default())];
+		let pool = Arc::new(pool(&nodes).await);
+
+		for _ in 0..4 {
+			send(&pool, "eth_getBalance").await.unwrap();
+		}
+		assert_eq!(hits(&nodes), [2, 2]);
+
+		assert!(is_pinned("eth_sendRawTransaction") && is_pinned("personal_sign"));
+		assert!(!is_pinned("eth_call"));
+		for _ in 0..3 {
+			send(&pool, "eth_sendRawTransaction").await.unwrap();
+		}
+		assert_eq!(hits(&nodes), [5, 2]);
+	}
+
+	#[tokio::test]
+	async fn test_failover() {
+		let nodes = [Arc::new(FakeNode::default()), Arc::new(FakeNode::default())];
+		let pool = Arc::new(pool(&nodes).await);
+		nodes[0].failing.store(true, Ordering::Relaxed);
+
+		// Idempotent calls go on to the next upstream, sending transactions can't be retried.
+		send(&pool, "eth_call").await.unwrap();
+		assert!(send(&pool, "eth_sendRawTransaction").await.is_err());
+		assert!(send(&pool, "eth_sendTransaction").await.is_err());
+		assert_eq!(hits(&nodes), [0, 1]);
+		assert_eq!(pool.upstreams[0].errors.load(Ordering::Relaxed), 3);
+	}
+
+	#[tokio::test]
+	async fn test_health_check() {
+		let nodes = [Arc::new(FakeNode::default()), Arc::new(FakeNode::default())];
+		let pool = Arc::new(pool(&nodes).await);
+		let healthy = |pool: &Pool| -> Vec<bool> {
+			pool.upstreams.iter().map(|upstream| upstream.healthy.load(Ordering::Relaxed)).collect()
+		};
+
+		// Lagging by max_block_lag is still fine, one block more is not.
+		nodes[0].block.store(100, Ordering::Relaxed);
+		nodes[1].block.store(95, Ordering::Relaxed);
+		pool.check().await;
+		assert_eq!(healthy(&pool), [true, true]);
+		nodes[1].block.store(94, Ordering::Relaxed);
+		pool.check().await;
+		assert_eq!(healthy(&pool), [true, false]);
+
+		// Unhealthy upstreams are only tried once the healthy ones failed.
+		for _ in 0..2 {
+			send(&pool, "eth_getBalance").await.unwrap();
+		}
+		assert_eq!(hits(&nodes), [2, 0]);
+
+		// The error rate counts from MIN_SAMPLES requests on.
+		nodes[1].block.store(100, Ordering::Relaxed);
+		pool.upstreams[0].requests.store(MIN_SAMPLES - 1, Ordering::Relaxed);
+		pool.upstreams[0].errors.store(MIN_SAMPLES - 1, Ordering::Relaxed);
+		pool.check().await;
+		assert_eq!(healthy(&pool), [true, true]);
+		pool.upstreams[0].requests.store(MIN_SAMPLES, Ordering::Relaxed);
+		pool.upstreams[0].errors.store(MIN_SAMPLES, Ordering::Relaxed);
+		pool.check().await;
+		assert_eq!(healthy(&pool), [false, true]);
+
+		// The counters start over, but an upstream which doesn't answer is unhealthy as well.
+		pool.check().await;
+		assert_eq!(healthy(&pool), [true, true]);
+		nodes[1].failing.store(true, Ordering::Relaxed);
+		pool.check().await;
+		assert_eq!(healthy(&pool), [true, false]);
+	}
+}
@@ -0,0 +1,339 @@
+use std::{
+	collections::BTreeMap,
+	fs,
+	path::{Path, PathBuf},
+	sync::RwLock,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+use futures::{future::BoxFuture, FutureExt};
+use log::{debug, info};
+use once_cell::sync::OnceCell;
+use rand::{thread_rng, Rng};
+use secp256k1::SecretKey;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use web3::{
+	signing::{Key, SecretKeyRef},
+	types::{Bytes, TransactionRequest, H160},
+};
+
+use super::{
+	transaction::{
+		parse_secret_key,
+		signing::{sign_with_key, LocalSigner, RemoteSigners, Signer},
+	},
+	wallet, Network,
+};
+use crate::{
+	config::SignersConfig,
+	error::Error::{AnyError, ConfigError, Conflict, InvalidParam, NotFound},
+	Result,
+};
+
+/// Passphrase of the keystore files, takes precedence over `passphrase_file` of the config.
+const PASSPHRASE_ENV: &str = "WEB3_KEYSTORE_PASSPHRASE";
+
+/// Directory in the keystore directory where the files of removed signers are moved to.
+const TRASH_DIR: &str = "trash";
+
+static SIGNERS: OnceCell<Signers> = OnceCell::new();
+
+/// Keys kept in Web3 Secret Storage (v3 keystore) files named `<alias>.json`, all unlocked at
+/// startup with one passphrase.
+struct Signers {
+	dir: PathBuf,
+	passphrase: Option<String>,
+	keys: RwLock<BTreeMap<String, SecretKey>>,
+	/// Accounts of the remote signer, if one is configured.
+	remote: Option<RemoteSigners>,
+}
+
+/// An unlocked keystore signer.
+struct KeystoreSigner {
+	alias: String,
+	key: SecretKey,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct SignerInfo {
+	alias: String,
+	#[schema(value_type = String)]
+	address: H160,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub(crate) struct ImportSignerRequest {
+	/// Name to reference the signer by, letters, digits, `_` and `-`.
+	alias: String,
+	/// Key to import, a new one is generated when left out.
+	secret_key: Option<String>,
+}
+
+impl Signers {
+	fn open(dir: &Path, passphrase: Option<String>) -> Result<Signers> {
+		fs::create_dir_all(dir).map_err(|e| AnyError(e.into()))?;
+		let mut keys = BTreeMap::new();
+		for entry in fs::read_dir(dir).map_err(|e| AnyError(e.into()))? {
+			let path = entry.map_err(|e| AnyError(e.into()))?.path();
+			let Some(alias) = keystore_alias(&path) else { continue };
+			let passphrase = passphrase.as_deref().ok_or_else(|| {
+				ConfigError(format!(
+					"keystore {} needs {} to be unlocked",
+					path.display(),
+					PASSPHRASE_ENV
+				))
+			})?;
+			let key = eth_keystore::decrypt_key(&path, passphrase)
+				.ok()
+				.and_then(|key| SecretKey::from_slice(&key).ok())
+				.ok_or_else(|| ConfigError(format!("unlock keystore {} failed", path.display())))?;
+			keys.insert(alias.to_string(), key);
+		}
+
+		info!("Unlocked {} signers from {}", keys.len(), dir.display());
+		Ok(Signers { dir: dir.to_path_buf(), passphrase, keys: RwLock::new(keys), remote: None })
+	}
+
+	fn list(&self) -> Vec<SignerInfo> {
+		let keys = self.keys.read().unwrap();
+		keys.iter().map(|(alias, key)| SignerInfo::new(alias, key)).collect()
+	}
+
+	fn import(&self, request: ImportSignerRequest) -> Result<SignerInfo> {
+		let passphrase = self.passphrase.as_deref().ok_or_else(|| {
+			ConfigError(format!("{} is required to import signers", PASSPHRASE_ENV))
+		})?;
+		if !is_valid_alias(&request.alias) {
+			return Err(InvalidParam(format!("alias: {}", request.alias)));
+		}
+		let key = match &request.secret_key {
+			Some(secret_key) => parse_secret_key(secret_key)?,
+			None => loop {
+				if let Ok(key) = SecretKey::from_slice(&thread_rng().gen::<[u8; 32]>()) {
+					break key;
+				}
+			},
+		};
+
+		let address = SecretKeyRef::new(&key).address();
+		check_new(&self.keys.read().unwrap(), &request.alias, address)?;
+
+		// Encrypting takes a while, the signers stay usable until the file is moved in place.
+		let name = format!(".{}.json.{}.tmp", request.alias, thread_rng().gen::<u64>());
+		eth_keystore::encrypt_key(
+			&self.dir,
+			&mut thread_rng(),
+			key.as_ref(),
+			passphrase,
+			Some(&name),
+		)
+		.map_err(|e| AnyError(e.into()))?;
+		let tmp = self.dir.join(name);
+		let mut keys = self.keys.write().unwrap();
+		let result = check_new(&keys, &request.alias, address).and_then(|_| {
+			fs::rename(&tmp, self.dir.join(format!("{}.json", request.alias)))
+				.map_err(|e| AnyError(e.into()))
+		});
+		if let Err(e) = result {
+			let _ = fs::remove_file(&tmp);
+			return Err(e);
+		}
+
+		info!("Imported signer {}, address: {:?}", request.alias, address);
+		keys.insert(request.alias.clone(), key);
+		Ok(SignerInfo { alias: request.alias, address })
+	}
+
+	/// Unload a signer, its keystore file is kept in the trash directory.
+	fn remove(&self, id: &str) -> Result<SignerInfo> {
+		let mut keys = self.keys.write().unwrap();
+		let (alias, key) = find(&keys, id)?;
+		let signer = SignerInfo::new(alias, key);
+		let trash = self.dir.join(TRASH_DIR);
+		fs::create_dir_all(&trash).map_err(|e| AnyError(e.into()))?;
+		let millis = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis();
+		let backup = trash.join(format!("{}.{}.json", signer.alias, millis));
+		fs::rename(self.dir.join(format!("{}.json", signer.alias)), &backup)
+			.map_err(|e| AnyError(e.into()))?;
+		keys.remove(&signer.alias);
+		info!("Removed signer {}, keystore moved to {}", signer.alias, backup.display());
+		Ok(signer)
+	}
+
+	fn signer(&self, id: &str) -> Result<KeystoreSigner> {
+		let (alias, key) =
+			find(&self.keys.read().unwrap(), id).map(|(alias, key)| (alias.clone(), *key))?;
+		Ok(KeystoreSigner { alias, key })
+	}
+
+	fn account_signer(&self, address: H160) -> Option<KeystoreSigner> {
+		let keys = self.keys.read().unwrap();
+		keys.iter()
+			.find(|(_, key)| SecretKeyRef::new(key).address() == address)
+			.map(|(alias, key)| KeystoreSigner { alias: alias.clone(), key: *key })
+	}
+}
+
+impl Signer for KeystoreSigner {
+	fn address(&self) -> H160 {
+		SecretKeyRef::new(&self.key).address()
+	}
+
+	fn sign_transaction<'a>(
+		&'a self,
+		network: &'a Network,
+		tx: TransactionRequest,
+	) -> BoxFuture<'a, Result<Bytes>> {
+		debug!("Sign transaction of {:?} with signer {}", tx.from, self.alias);
+		sign_with_key(network, tx, &self.key).boxed()
+	}
+
+	fn secret_key(&self) -> Option<&SecretKey> {
+		Some(&self.key)
+	}
+}
+
+impl SignerInfo {
+	fn new(alias: &str, key: &SecretKey) -> SignerInfo {
+		SignerInfo { alias: alias.to_string(), address: SecretKeyRef::new(key).address() }
+	}
+}
+
+/// Neither the alias nor the address of a new signer may be imported already.
+fn check_new(keys: &BTreeMap<String, SecretKey>, alias: &str, address: H160) -> Result<()> {
+	if keys.contains_key(alias) {
+		return Err(Conflict(format!("signer: {} exists", alias)));
+	}
+	match keys.iter().find(|(_, key)| SecretKeyRef::new(key).address() == address) {
+		Some((alias, _)) => {
+			Err(Conflict(format!("{:?} is imported as signer: {}", address, alias)))
+		}
+		None => Ok(()),
+	}
+}
+
+/// A signer by alias or address.
+fn find<'a>(
+	keys: &'a BTreeMap<String, SecretKey>,
+	id: &str,
+) -> Result<(&'a String, &'a SecretKey)> {
+	let signer = match id.parse::<H160>() {
+		Ok(address) => keys.iter().find(|(_, key)| SecretKeyRef::new(key).address() == address),
+		Err(_) => keys.get_key_value(id),
+	};
+	signer.ok_or_else(|| NotFound(format!("signer: {}", id)))
+}
+
+fn keystore_alias(path: &Path) -> Option<&str> {
+	match path.extension()?.to_str()? {
+		"json" => path.file_stem()?.to_str().filter(|alias| is_valid_alias(alias)),
+		_ => None,
+	}
+}
+
+fn is_valid_alias(alias: &str) -> bool {
+	(1..=64).contains(&alias.len())
+		&& !alias.starts_with("0x")
+		&& alias.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Unlock the keystore files and connect to the remote signer, must be called once before the
+/// signers are used.
+pub(crate) async fn init(config: &SignersConfig) -> Result<()> {
+	let passphrase = match (std::env::var(PASSPHRASE_ENV).ok(), &config.passphrase_file) {
+		(Some(passphrase), _) => Some(passphrase),
+		(None, Some(path)) => Some(
+			fs::read_to_string(path)
+				.map_err(|e| ConfigError(format!("read {} failed, {}", path.display(), e)))?
+				.trim_end_matches(['\r', '\n'])
+				.to_string(),
+		),
+		(None, None) => None,
+	};
+	let mut signers = Signers::open(&config.keystore_dir, passphrase)?;
+	if let Some(url) = &config.remote_url {
+		signers.remote = Some(RemoteSigners::connect(url, &config.remote_accounts).await?);
+	}
+	SIGNERS.set(signers).map_err(|_| ConfigError("signers are initialized twice".to_string()))
+}
+
+fn signers() -> &'static Signers {
+	SIGNERS.get().expect("signers are not initialized")
+}
+
+pub(crate) fn list() -> Vec<SignerInfo> {
+	signers().list()
+}
+
+/// Import a signer, its key is encrypted off the async runtime.
+pub(crate) async fn import(request: ImportSignerRequest) -> Result<SignerInfo> {
+	tokio::task::spawn_blocking(move || signers().import(request))
+		.await
+		.map_err(|e| AnyError(e.into()))?
+}
+
+pub(crate) fn remove(id: &str) -> Result<SignerInfo> {
+	signers().remove(id)
+}
+
+/// A keystore signer by alias or address, or the signer of a wallet account or an account of
+/// the remote signer by address.
+pub(crate) fn by_id(id: &str) -> Result<Box<dyn Signer>> {
+	match signers().signer(id) {
+		Ok(signer) => Ok(Box::new(signer)),
+		Err(e) => id.parse().ok().and_then(by_account).ok_or(e),
+	}
+}
+
+/// The signer of an account which is a keystore signer, a wallet account or an account of the
+/// remote signer.
+pub(crate) fn by_account(address: H160) -> Option<Box<dyn Signer>> {
+	let signers = signers();
+	if let Some(signer) = signers.account_signer(address) {
+		return Some(Box::new(signer));
+	}
+	if let Some(key) = wallet::key(address) {
+		return Some(Box::new(LocalSigner::new(key)));
+	}
+	let remote = signers.remote.as_ref()?.signer(address)?;
+	Some(Box::new(remote))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::fs;
+
+	use super::{ImportSignerRequest, Signers, TRASH_DIR};
+
+	#[test]
+	fn test_keystore_signers() {
+		let dir = std::env::temp_dir().join(format!("signers-{}", std::process::id()));
+		let secret_key = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+		let address = "0x2c7536e3605d9c16a7a3d7b1898e529396a65c23";
+		let request = |alias: &str| ImportSignerRequest {
+			alias: alias.to_string(),
+			secret_key: Some(secret_key.to_string()),
+		};
+
+		let signers = Signers::open(&dir, Some("secret".to_string())).unwrap();
+		assert!(signers.import(request("0xdead")).is_err());
+		signers.import(request("alice")).unwrap();
+		assert!(signers.import(request("alice")).is_err());
+		assert!(signers.import(request("bob")).is_err());
+
+		let signers = Signers::open(&dir, Some("secret".to_string())).unwrap();
+		assert_eq!(signers.signer("alice").unwrap().key, signers.signer(address).unwrap().key);
+		assert_eq!(hex::encode(signers.signer("alice").unwrap().key.as_ref()), secret_key);
+		assert!(Signers::open(&dir, Some("wrong".to_string())).is_err());
+		assert!(Signers::open(&dir, None).is_err());
+
+		signers.remove(address).unwrap();
+		assert!(signers.signer("alice").is_err());
+		assert!(Signers::open(&dir, None).unwrap().list().is_empty());
+		// The keystore file is kept aside, and no temporary file of an import is left behind.
+		assert_eq!(fs::read_dir(dir.join(TRASH_DIR)).unwrap().count(), 1);
+		assert_eq!(fs::read_dir(&dir).unwrap().count(), 1);
+		fs::remove_dir_all(dir).unwrap();
+	}
+}
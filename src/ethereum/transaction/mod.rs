@@ -0,0 +1,434 @@
+use secp256k1::SecretKey;
+use serde::{de::Error as _, Deserialize, Deserializer, Serialize};
+use serde_json::Value as JsonValue;
+use utoipa::ToSchema;
+use web3::types::{
+	AccessList, BlockNumber, Bytes, CallRequest, FeeHistory, TransactionRequest, H160, H256, U256,
+};
+
+use crate::{error::Error::*, Result};
+
+use self::{
+	signing::{LocalSigner, Signer},
+	tracker::TxKind,
+};
+use super::{amount::Amount, signer, Network};
+
+pub(crate) mod nonce;
+pub(crate) mod offline;
+pub(crate) mod replace;
+pub(crate) mod signing;
+pub(crate) mod tracker;
+
+/// Blocks of the fee history which priority fees are suggested from.
+const FEE_HISTORY_BLOCKS: u64 = 10;
+/// Gas of a plain transfer.
+const TRANSFER_GAS: u64 = 21_000;
+
+/// A transaction of type 0 (legacy), 1 (EIP-2930 access list) or 2 (EIP-1559), the fees which
+/// are left out are suggested from the node.
+#[derive(Debug, Default, Serialize, Deserialize, ToSchema)]
+pub struct TxRequest {
+	/// Sender, signed transactions are sent from the account of the signer or the secret key.
+	from: Option<String>,
+	/// Receiver, left out to create a contract.
+	to: Option<String>,
+	/// `0x` hex payload.
+	#[schema(value_type = String)]
+	data: Option<Bytes>,
+	/// Transaction type, derived from the fees and access list when left out.
+	#[serde(rename = "type", default, deserialize_with = "de_quantity")]
+	#[schema(value_type = u64)]
+	transaction_type: Option<U256>,
+	/// Must match the chain id of the network.
+	#[serde(default, deserialize_with = "de_quantity")]
+	#[schema(value_type = u64)]
+	chain_id: Option<U256>,
+	/// Addresses and storage keys like `[{"address": "0x..", "storageKeys": ["0x.."]}]`.
+	#[schema(value_type = Vec<Object>)]
+	access_list: Option<AccessList>,
+	#[serde(flatten)]
+	options: TxOptions,
+	/// Alias or address of a signer the transaction is signed by.
+	signer: Option<String>,
+	/// Key to sign with instead of a signer, it leaves the client in the request body.
+	secret_key: Option<String>,
+}
+
+impl TxRequest {
+	/// The transaction as requested, without any of the suggested fees or gas.
+	fn transaction(&self, network: &Network, from: H160) -> Result<TransactionRequest> {
+		if self.chain_id.is_some_and(|chain_id| chain_id != network.chain_id) {
+			return Err(InvalidParam(format!(
+				"chain_id: {} doesn't match network {}, chain id: {}",
+				self.chain_id.unwrap_or_default(),
+				network.name,
+				network.chain_id
+			)));
+		}
+		let to = match &self.to {
+			Some(to) => Some(to.parse().map_err(|_| InvalidParam(format!("to: {}", to)))?),
+			None => None,
+		};
+
+		let mut tx = TransactionRequest {
+			from,
+			to,
+			data: self.data.clone(),
+			access_list: self.access_list.clone(),
+			..Default::default()
+		};
+		self.options.apply(&mut tx)?;
+
+		let tx_type = match self.transaction_type {
+			Some(tx_type) if tx_type > 2.into() => {
+				return Err(InvalidParam(format!("type: {} is not supported", tx_type)))
+			}
+			tx_type => tx_type.map(|tx_type| tx_type.as_u64()),
+		};
+		let eip1559 = tx.max_fee_per_gas.is_some() || tx.max_priority_fee_per_gas.is_some();
+		match tx_type {
+			Some(0) if tx.access_list.is_some() => {
+				return Err(InvalidParam("access_list requires type 1 or 2".to_string()))
+			}
+			Some(0 | 1) if eip1559 => {
+				return Err(InvalidParam(
+					"max_fee_per_gas and max_priority_fee_per_gas require type 2".to_string(),
+				))
+			}
+			Some(2) if tx.gas_price.is_some() => {
+				return Err(InvalidParam("gas_price requires type 0 or 1".to_string()))
+			}
+			_ => {}
+		}
+		tx.transaction_type = tx_type.map(Into::into);
+		Ok(tx)
+	}
+}
+
+/// Gas, fee, value and nonce of a transaction, quantities are json numbers, decimal or `0x` hex
+/// strings.
+#[derive(Debug, Default, Clone, Serialize, Deserialize, ToSchema)]
+pub(crate) struct TxOptions {
+	/// Gas limit, estimated when left out.
+	#[serde(default, deserialize_with = "de_quantity")]
+	#[schema(value_type = String)]
+	pub gas: Option<U256>,
+	/// Legacy gas price in wei, excludes the EIP-1559 fees.
+	#[serde(default, deserialize_with = "de_quantity")]
+	#[schema(value_type = String)]
+	pub gas_price: Option<U256>,
+	#[serde(default, deserialize_with = "de_quantity")]
+	#[schema(value_type = String)]
+	pub max_fee_per_gas: Option<U256>,
+	#[serde(default, deserialize_with = "de_quantity")]
+	#[schema(value_type = String)]
+	pub max_priority_fee_per_gas: Option<U256>,
+	/// Sent along to payable functions and constructors, in wei unless a unit is given like
+	/// `0.5 ether` or `20 gwei`.
+	#[schema(value_type = String)]
+	pub value: Option<Amount>,
+	#[serde(default, deserialize_with = "de_quantity")]
+	#[schema(value_type = String)]
+	pub nonce: Option<U256>,
+}
+
+impl TxOptions {
+	/// Apply the options to a transaction, the node fills in the fees which are left out.
+	pub(crate) fn apply(&self, tx: &mut TransactionRequest) -> Result<()> {
+		let eip1559 = self.max_fee_per_gas.is_some() || self.max_priority_fee_per_gas.is_some();
+		if eip1559 && self.gas_price.is_some() {
+			return Err(InvalidParam(
+				"gas_price can't be combined with max_fee_per_gas or max_priority_fee_per_gas"
+					.to_string(),
+			));
+		}
+		if let (Some(max_fee), Some(priority_fee)) =
+			(self.max_fee_per_gas, self.max_priority_fee_per_gas)
+		{
+			if priority_fee > max_fee {
+				return Err(InvalidParam(
+					"max_priority_fee_per_gas exceeds max_fee_per_gas".to_string(),
+				));
+			}
+		}
+
+		tx.gas = self.gas.or(tx.gas);
+		tx.gas_price = self.gas_price;
+		tx.max_fee_per_gas = self.max_fee_per_gas;
+		tx.max_priority_fee_per_gas = self.max_priority_fee_per_gas;
+		tx.value = self.value.map(Amount::wei).or(tx.value);
+		tx.nonce = self.nonce.or(tx.nonce);
+		if eip1559 {
+			tx.transaction_type = Some(2.into());
+		}
+		Ok(())
+	}
+}
+
+/// Estimate the gas limit of a transaction which has none, padded by the network's multiplier.
+pub(crate) async fn fill_gas(network: &Network, tx: &mut TransactionRequest) -> Result<()> {
+	if tx.gas.is_some() {
+		return Ok(());
+	}
+
+	let call = CallRequest {
+		from: Some(tx.from),
+		to: tx.to,
+		gas: None,
+		gas_price: tx.gas_price,
+		value: tx.value,
+		data: tx.data.clone(),
+		transaction_type: tx.transaction_type,
+		access_list: tx.access_list.clone(),
+		max_fee_per_gas: tx.max_fee_per_gas,
+		max_priority_fee_per_gas: tx.max_priority_fee_per_gas,
+	};
+	let estimated = network.web3.eth().estimate_gas(call, None).await?;
+	let permille = (network.gas_multiplier * 1000.0).round() as u64;
+	tx.gas = Some(estimated.saturating_mul(permille.into()) / 1000);
+	Ok(())
+}
+
+/// Settle the type of a transaction and fill in the fees which are left out. EIP-1559 fees are
+/// suggested from the fee history, chains without a base fee get the node's legacy gas price.
+pub(crate) async fn fill_fees(network: &Network, tx: &mut TransactionRequest) -> Result<()> {
+	let tx_type = tx.transaction_type.map(|tx_type| tx_type.as_u64());
+	let legacy = match tx_type {
+		Some(tx_type) => tx_type < 2,
+		None => tx.gas_price.is_some(),
+	};
+	let fees = match legacy {
+		true => None,
+		false => suggest_fees(network).await,
+	};
+
+	match fees {
+		Some((base_fee, tip)) => {
+			let tip = tx.max_priority_fee_per_gas.unwrap_or(tip);
+			let max_fee =
+				*tx.max_fee_per_gas.get_or_insert(base_fee.saturating_mul(2.into()) + tip);
+			tx.max_priority_fee_per_gas = Some(tip.min(max_fee));
+			tx.transaction_type = Some(2.into());
+		}
+		None if tx_type == Some(2) || tx.max_fee_per_gas.is_some() => {
+			return Err(InvalidParam(format!(
+				"network: {} doesn't support EIP-1559 transactions",
+				network.name
+			)))
+		}
+		None => {
+			if tx.gas_price.is_none() {
+				tx.gas_price = Some(network.web3.eth().gas_price().await?);
+			}
+			// An access list needs type 1, which is kept as well when asked for without one.
+			let typed = tx_type == Some(1) || tx.access_list.is_some();
+			tx.transaction_type = typed.then(|| 1.into());
+		}
+	}
+	Ok(())
+}
+
+/// Next base fee and median priority fee of the recent blocks, `None` without EIP-1559.
+async fn suggest_fees(network: &Network) -> Option<(U256, U256)> {
+	let history = network
+		.web3
+		.eth()
+		.fee_history(FEE_HISTORY_BLOCKS.into(), BlockNumber::Latest, Some(vec![50.0]))
+		.await
+		.ok()?;
+	fees_from_history(&history)
+}
+
+fn fees_from_history(history: &FeeHistory) -> Option<(U256, U256)> {
+	let base_fee = history.base_fee_per_gas.last().copied().filter(|fee| !fee.is_zero())?;
+	let mut tips = history
+		.reward
+		.iter()
+		.flatten()
+		.filter_map(|rewards| rewards.first().copied())
+		.collect::<Vec<_>>();
+	tips.sort();
+	Some((base_fee, tips.get(tips.len() / 2).copied().unwrap_or_default()))
+}
+
+/// Send a transaction, signed locally when the request names a signer or a key or the sender is
+/// a local account, otherwise by the node.
+#[inline]
+pub async fn send_transaction(network: &Network, tx_request: TxRequest) -> Result<H256> {
+	let (from, signer) = sender(
+		tx_request.from.as_deref(),
+		tx_request.signer.as_deref(),
+		tx_request.secret_key.as_deref(),
+	)?;
+
+	let mut tx = tx_request.transaction(network, from)?;
+	let hash = match &signer {
+		Some(signer) => send_signed(network, tx, signer.as_ref()).await?,
+		None => {
+			fill_fees(network, &mut tx).await?;
+			fill_gas(network, &mut tx).await?;
+			network.web3.eth().send_transaction(tx).await?
+		}
+	};
+	network.tracker.track(hash, from, TxKind::Send, 0);
+	Ok(hash)
+}
+
+#[inline]
+pub async fn send_raw_transaction(network: &Network, tx_request: TxRequest) -> Result<H256> {
+	if tx_request.signer.is_none() && tx_request.secret_key.is_none() {
+		return Err(InvalidParam("signer or secret_key is required".to_string()));
+	}
+	send_transaction(network, tx_request).await
+}
+
+/// The signer named by alias or address, or a key given in the request. Otherwise a sender
+/// which is a keystore signer, a wallet account or an account of the remote signer signs with
+/// its own signer, `None` leaves the signing to the node.
+pub(crate) fn resolve_signer(
+	signer: Option<&str>,
+	secret_key: Option<&str>,
+	from: Option<H160>,
+) -> Result<Option<Box<dyn Signer>>> {
+	match (signer, secret_key) {
+		(Some(_), Some(_)) => Err(InvalidParam("signer excludes secret_key".to_string())),
+		(Some(signer), None) => signer::by_id(signer).map(Some),
+		(None, Some(secret_key)) => {
+			let key = parse_secret_key(secret_key)?;
+			Ok(Some(Box::new(LocalSigner::new(key))))
+		}
+		(None, None) => Ok(from.and_then(signer::by_account)),
+	}
+}
+
+/// The account a transaction is sent from and the signer it's signed by, see
+/// [`resolve_signer`].
+pub(crate) fn sender(
+	from: Option<&str>,
+	signer: Option<&str>,
+	secret_key: Option<&str>,
+) -> Result<(H160, Option<Box<dyn Signer>>)> {
+	let from = match from {
+		Some(from) => {
+			Some(from.parse::<H160>().map_err(|_| InvalidParam(format!("from: {}", from)))?)
+		}
+		None => None,
+	};
+	let signer = resolve_signer(signer, secret_key, from)?;
+	match signer.as_ref().map(|signer| signer.address()) {
+		Some(account) if from.is_some_and(|from| from != account) => Err(InvalidParam(format!(
+			"from: {:?} is not the account of the signer",
+			from.unwrap_or_default()
+		))),
+		Some(account) => Ok((account, signer)),
+		None => Ok((from.ok_or_else(|| InvalidParam("from is required".to_string()))?, None)),
+	}
+}
+
+/// Sign a transaction with a nonce reserved for its sender unless it has one, and send it.
+pub(crate) async fn send_signed(
+	network: &Network,
+	mut tx: TransactionRequest,
+	signer: &dyn Signer,
+) -> Result<H256> {
+	let reservation = match tx.nonce {
+		Some(_) => None,
+		None => Some(network.nonces.reserve(network, tx.from).await?),
+	};
+	tx.nonce = reservation.as_ref().map(|reservation| reservation.nonce).or(tx.nonce);
+	fill_fees(network, &mut tx).await?;
+	fill_gas(network, &mut tx).await?;
+
+	let hash = sign_and_send(network, tx, signer).await?;
+	if let Some(reservation) = reservation {
+		reservation.sent();
+	}
+	Ok(hash)
+}
+
+pub(crate) fn parse_secret_key(secret_key: &str) -> Result<SecretKey> {
+	secret_key
+		.trim_start_matches("0x")
+		.parse()
+		.map_err(|_| InvalidParam("secret_key is invalid".to_string()))
+}
+
+/// Sign a transaction with every field filled in and send it.
+async fn sign_and_send(
+	network: &Network,
+	tx: TransactionRequest,
+	signer: &dyn Signer,
+) -> Result<H256> {
+	let raw = signer.sign_transaction(network, tx).await?;
+	let hash = network.web3.eth().send_raw_transaction(raw).await?;
+	Ok(hash)
+}
+
+fn de_quantity<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<U256>, D::Error> {
+	let value = Option::<JsonValue>::deserialize(deserializer)?.unwrap_or_default();
+	let quantity = match &value {
+		JsonValue::Null => return Ok(None),
+		JsonValue::Number(num) => num.as_u64().map(U256::from),
+		JsonValue::String(s) => match s.strip_prefix("0x") {
+			Some(hex) => U256::from_str_radix(hex, 16).ok(),
+			None => U256::from_dec_str(s).ok(),
+		},
+		_ => None,
+	};
+	quantity.map(Some).ok_or_else(|| D::Error::custom(format!("{} is not a valid quantity", value)))
+}
+
+#[cfg(test)]
+mod tests {
+	use web3::types::{BlockNumber, FeeHistory, TransactionRequest};
+
+	use super::{fees_from_history, fill_fees, TxOptions};
+	use crate::ethereum::testing;
+
+	#[test]
+	fn test_tx_options() {
+		let options: TxOptions = serde_json::from_str(
+			r#"{"gas": 21000, "max_fee_per_gas": "30000000000", "value": "0xde0b6b3a7640000"}"#,
+		)
+		.unwrap();
+		let mut tx = TransactionRequest::default();
+		options.apply(&mut tx).unwrap();
+		assert_eq!(tx.gas, Some(21000.into()));
+		assert_eq!(tx.value, Some(1_000_000_000_000_000_000u64.into()));
+		assert_eq!(tx.transaction_type, Some(2.into()));
+
+		let options: TxOptions =
+			serde_json::from_str(r#"{"gas_price": 1, "max_priority_fee_per_gas": 1}"#).unwrap();
+		assert!(options.apply(&mut tx).is_err());
+		assert!(serde_json::from_str::<TxOptions>(r#"{"nonce": -1}"#).is_err());
+	}
+
+	#[tokio::test]
+	async fn test_fill_legacy_fees() {
+		// With the gas price given nothing is asked from the node.
+		let network = testing::network("http://127.0.0.1:1");
+		let mut tx = TransactionRequest { gas_price: Some(1.into()), ..Default::default() };
+		fill_fees(&network, &mut tx).await.unwrap();
+		assert_eq!(tx.transaction_type, None);
+
+		tx.transaction_type = Some(1.into());
+		fill_fees(&network, &mut tx).await.unwrap();
+		assert_eq!(tx.transaction_type, Some(1.into()));
+		assert!(tx.max_fee_per_gas.is_none());
+	}
+
+	#[test]
+	fn test_fees_from_history() {
+		let mut history = FeeHistory {
+			oldest_block: BlockNumber::Number(1.into()),
+			base_fee_per_gas: vec![10.into(), 12.into(), 14.into()],
+			gas_used_ratio: vec![0.5, 0.9],
+			reward: Some(vec![vec![3.into()], vec![1.into()], vec![2.into()]]),
+		};
+		assert_eq!(fees_from_history(&history), Some((14.into(), 2.into())));
+
+		history.base_fee_per_gas = vec![0.into(); 3];
+		assert_eq!(fees_from_history(&history), None);
+	}
+}
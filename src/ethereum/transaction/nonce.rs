@@ -0,0 +1,332 @@
+use std::{
+	collections::{BTreeSet, HashMap},
+	sync::Mutex,
+};
+
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use web3::types::{BlockNumber, TransactionRequest, H160, H256, U256};
+
+use crate::{error::Error::InvalidParam, Result};
+
+use super::{
+	fill_fees,
+	replace::{fill_replacement_fees, pending_transaction, ReplaceTxRequest},
+	resolve_signer, sign_and_send,
+	signing::Signer,
+	tracker::TxKind,
+	Network, TRANSFER_GAS,
+};
+
+/// Nonces of the accounts which sign locally, handed out without waiting for the node so that
+/// concurrent transactions of one account don't collide.
+#[derive(Debug, Default)]
+pub(crate) struct NonceManager {
+	accounts: Mutex<HashMap<H160, AccountNonces>>,
+}
+
+#[derive(Debug, Default)]
+struct AccountNonces {
+	/// Next nonce above every reserved one.
+	next: U256,
+	/// Nonces which were reserved but not sent while a later one was, the chain is stuck
+	/// behind the lowest of them until it is filled.
+	gaps: BTreeSet<U256>,
+}
+
+/// A nonce which is released as a gap unless the transaction using it was sent.
+#[must_use]
+pub(crate) struct NonceReservation<'a> {
+	manager: &'a NonceManager,
+	account: H160,
+	pub nonce: U256,
+	sent: bool,
+}
+
+impl NonceReservation<'_> {
+	pub(crate) fn sent(mut self) {
+		self.sent = true;
+	}
+}
+
+impl Drop for NonceReservation<'_> {
+	fn drop(&mut self) {
+		if !self.sent {
+			self.manager.release(self.account, self.nonce);
+		}
+	}
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct NonceStatus {
+	#[schema(value_type = String)]
+	account: H160,
+	/// Transactions of the account which are mined.
+	#[schema(value_type = String)]
+	latest: U256,
+	/// Transactions of the account which are mined or known to the node.
+	#[schema(value_type = String)]
+	pending: U256,
+	/// Nonce the next transaction signed by this service gets, unless a gap is filled first.
+	#[schema(value_type = String)]
+	next: U256,
+	#[schema(value_type = Vec<String>)]
+	gaps: Vec<U256>,
+}
+
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub(crate) struct FillNoncesRequest {
+	/// Alias or address of the signer of the account.
+	pub signer: Option<String>,
+	/// Key of the account, node accounts are sent from by the node when neither is given.
+	pub secret_key: Option<String>,
+	/// Pending nonces which are stuck, they are replaced by transfers paying the fees of the
+	/// stuck transactions with the bump nodes require.
+	#[serde(default)]
+	pub cancel: Vec<u64>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct FilledNonce {
+	#[schema(value_type = String)]
+	nonce: U256,
+	#[schema(value_type = String)]
+	hash: H256,
+}
+
+impl NonceManager {
+	/// Reserve the lowest gap or the next nonce of an account, synced with the transactions
+	/// the node knows of so that transactions sent elsewhere are skipped.
+	pub(crate) async fn reserve(
+		&self,
+		network: &Network,
+		account: H160,
+	) -> Result<NonceReservation<'_>> {
+		let pending =
+			network.web3.eth().transaction_count(account, Some(BlockNumber::Pending)).await?;
+
+		let mut accounts = self.accounts.lock().unwrap();
+		let nonces = accounts.entry(account).or_default();
+		nonces.sync(pending);
+		let nonce = match nonces.gaps.pop_first() {
+			Some(gap) => {
+				info!("Fill nonce gap {} of {:?}", gap, account);
+				gap
+			}
+			None => {
+				nonces.next += U256::one();
+				nonces.next - 1
+			}
+		};
+		Ok(NonceReservation { manager: self, account, nonce, sent: false })
+	}
+
+	/// Take a gap of an account to fill it, `None` unless the nonce is a gap.
+	pub(crate) fn claim(&self, account: H160, nonce: U256) -> Option<NonceReservation<'_>> {
+		let mut accounts = self.accounts.lock().unwrap();
+		match accounts.get_mut(&account).is_some_and(|nonces| nonces.gaps.remove(&nonce)) {
+			true => Some(NonceReservation { manager: self, account, nonce, sent: false }),
+			false => None,
+		}
+	}
+
+	/// Nonces of an account on the node and in this service, gaps the node already has a
+	/// transaction for are dropped.
+	pub(crate) async fn status(&self, network: &Network, account: H160) -> Result<NonceStatus> {
+		let eth = network.web3.eth();
+		let latest = eth.transaction_count(account, Some(BlockNumber::Latest)).await?;
+		let pending = eth.transaction_count(account, Some(BlockNumber::Pending)).await?;
+
+		let mut accounts = self.accounts.lock().unwrap();
+		let nonces = accounts.entry(account).or_default();
+		nonces.sync(pending);
+		let gaps = nonces.gaps.iter().copied().collect();
+		Ok(NonceStatus { account, latest, pending, next: nonces.next, gaps })
+	}
+
+	fn release(&self, account: H160, nonce: U256) {
+		let mut accounts = self.accounts.lock().unwrap();
+		let nonces = accounts.entry(account).or_default();
+		nonces.gaps.insert(nonce);
+		while !nonces.next.is_zero() && nonces.gaps.remove(&(nonces.next - 1)) {
+			nonces.next -= U256::one();
+		}
+		if nonces.gaps.contains(&nonce) {
+			warn!("Nonce {} of {:?} is left as a gap", nonce, account);
+		}
+	}
+}
+
+/// Fill the gaps of an account and cancel its stuck nonces with transfers of nothing to itself.
+pub(crate) async fn fill_nonces(
+	network: &Network,
+	account: H160,
+	request: FillNoncesRequest,
+) -> Result<Vec<FilledNonce>> {
+	let signer =
+		resolve_signer(request.signer.as_deref(), request.secret_key.as_deref(), Some(account))?;
+	if signer.as_ref().is_some_and(|signer| signer.address() != account) {
+		return Err(InvalidParam(format!("signer is not the one of {:?}", account)));
+	}
+	let status = network.nonces.status(network, account).await?;
+	if let Some(nonce) = request
+		.cancel
+		.iter()
+		.find(|nonce| !(status.latest..status.pending).contains(&(**nonce).into()))
+	{
+		return Err(InvalidParam(format!("nonce: {} is not pending", nonce)));
+	}
+
+	let mut filled = Vec::new();
+	for nonce in status.gaps {
+		if let Some(reservation) = network.nonces.claim(account, nonce) {
+			let hash = send_transfer(network, account, nonce, signer.as_deref(), false).await?;
+			reservation.sent();
+			filled.push(FilledNonce { nonce, hash });
+		}
+	}
+	for nonce in request.cancel.into_iter().map(U256::from) {
+		let hash = send_transfer(network, account, nonce, signer.as_deref(), true).await?;
+		filled.push(FilledNonce { nonce, hash });
+	}
+	info!("Filled nonces of {:?}: {:?}", account, filled);
+	Ok(filled)
+}
+
+async fn send_transfer(
+	network: &Network,
+	account: H160,
+	nonce: U256,
+	signer: Option<&dyn Signer>,
+	replace: bool,
+) -> Result<H256> {
+	let mut tx = TransactionRequest {
+		from: account,
+		to: Some(account),
+		gas: Some(TRANSFER_GAS.into()),
+		value: Some(U256::zero()),
+		nonce: Some(nonce),
+		..Default::default()
+	};
+	if replace {
+		let original = pending_transaction(network, account, nonce).await?;
+		fill_replacement_fees(network, &original, &ReplaceTxRequest::default(), &mut tx).await?;
+	} else {
+		fill_fees(network, &mut tx).await?;
+	}
+
+	let hash = match signer {
+		Some(signer) => sign_and_send(network, tx, signer).await?,
+		None => network.web3.eth().send_transaction(tx).await?,
+	};
+	network.tracker.track(hash, account, TxKind::Send, 0);
+	Ok(hash)
+}
+
+impl AccountNonces {
+	fn sync(&mut self, pending: U256) {
+		self.next = self.next.max(pending);
+		self.gaps.retain(|gap| *gap >= pending);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::{Arc, Mutex};
+
+	use serde_json::{json, Value};
+	use web3::types::{H160, U256};
+
+	use super::{send_transfer, AccountNonces, NonceManager, NonceReservation};
+	use crate::ethereum::testing;
+
+	#[test]
+	fn test_release_nonces() {
+		let manager = NonceManager::default();
+		let account = H160::zero();
+		manager
+			.accounts
+			.lock()
+			.unwrap()
+			.insert(account, AccountNonces { next: 8.into(), gaps: Default::default() });
+		let reserve = |nonce: u64| NonceReservation {
+			manager: &manager,
+			account,
+			nonce: nonce.into(),
+			sent: false,
+		};
+
+		// 5 fails while 6 and 7 are sent, 7 is the last one so it gives its nonce back.
+		drop(reserve(5));
+		reserve(6).sent();
+		drop(reserve(7));
+		let nonces = manager.accounts.lock().unwrap().remove(&account).unwrap();
+		assert_eq!(nonces.next, 7.into());
+		assert_eq!(nonces.gaps.into_iter().collect::<Vec<_>>(), vec![U256::from(5)]);
+
+		let mut nonces = AccountNonces { next: 7.into(), gaps: [5.into()].into() };
+		nonces.sync(9.into());
+		assert_eq!(nonces.next, 9.into());
+		assert!(nonces.gaps.is_empty());
+	}
+
+	#[tokio::test]
+	async fn test_cancel_fees() {
+		// The stuck transactions pay more than the node suggests now.
+		let sent = Arc::new(Mutex::new(vec![]));
+		let node = sent.clone();
+		let url = testing::serve(move |request: Value| {
+			let sent = node.clone();
+			async move {
+				let stuck = |nonce: u64, fees: Value| {
+					let mut tx = json!({
+						"hash": format!("0x{:064x}", nonce),
+						"nonce": format!("{:#x}", nonce),
+						"from": format!("{:?}", H160::zero()),
+						"to": format!("{:?}", H160::repeat_byte(0x11)),
+						"value": "0x0",
+						"gas": "0x5208",
+						"input": "0x",
+					});
+					tx.as_object_mut().unwrap().extend(fees.as_object().unwrap().clone());
+					tx
+				};
+				Ok(match request["method"].as_str() {
+					Some("txpool_contentFrom") => json!({
+						"pending": {
+							"7": stuck(7, json!({"type": "0x2", "maxFeePerGas": "0x64",
+								"maxPriorityFeePerGas": "0xa"})),
+							"8": stuck(8, json!({"type": "0x0", "gasPrice": "0x32"})),
+						},
+						"queued": {},
+					}),
+					Some("eth_feeHistory") => json!({
+						"oldestBlock": "0x1",
+						"baseFeePerGas": ["0x1", "0x1"],
+						"gasUsedRatio": [0.5],
+						"reward": [["0x1"]],
+					}),
+					Some("eth_gasPrice") => json!("0x1"),
+					Some("eth_sendTransaction") => {
+						sent.lock().unwrap().push(request["params"][0].clone());
+						json!(format!("0x{:064x}", 1))
+					}
+					method => panic!("unexpected {:?}", method),
+				})
+			}
+		})
+		.await;
+		let network = testing::network(&url);
+
+		for nonce in [7u64, 8] {
+			send_transfer(&network, H160::zero(), nonce.into(), None, true).await.unwrap();
+		}
+		assert!(send_transfer(&network, H160::zero(), 9.into(), None, true).await.is_err());
+		let sent = sent.lock().unwrap();
+		assert_eq!(sent[0]["maxFeePerGas"], "0x6e");
+		assert_eq!(sent[0]["maxPriorityFeePerGas"], "0xb");
+		assert_eq!(sent[1]["gasPrice"], "0x37");
+		assert_eq!(sent[1]["to"], format!("{:?}", H160::zero()));
+	}
+}
@@ -0,0 +1,320 @@
+use std::fmt::Display;
+
+use log::info;
+use rlp::{Rlp, RlpStream};
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+use web3::{
+	signing::{keccak256, recover},
+	types::{AccessList, AccessListItem, BlockNumber, Bytes, TransactionRequest, H160, H256, U256},
+};
+
+use crate::{error::Error::InvalidParam, Result};
+
+use super::{sender, tracker::TxKind, Amount, Network, TxRequest};
+
+/// A transaction signed without being sent.
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct SignedTx {
+	/// `0x` hex of the RLP encoding, prefixed by the type for typed transactions.
+	#[schema(value_type = String)]
+	raw: Bytes,
+	#[schema(value_type = String)]
+	hash: H256,
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub(crate) struct BroadcastRequest {
+	/// `0x` hex of a signed transaction.
+	#[schema(value_type = String)]
+	raw: Bytes,
+}
+
+/// A signed transaction decoded from its RLP encoding.
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct DecodedTx {
+	#[schema(value_type = String)]
+	hash: H256,
+	#[serde(rename = "type")]
+	transaction_type: u64,
+	/// Left out by legacy transactions without replay protection.
+	pub(super) chain_id: Option<u64>,
+	/// Recovered from the signature.
+	#[schema(value_type = String)]
+	pub(super) from: H160,
+	#[schema(value_type = String)]
+	pub(super) nonce: U256,
+	#[schema(value_type = String)]
+	to: Option<H160>,
+	#[schema(value_type = String)]
+	value: Amount,
+	#[schema(value_type = String)]
+	gas: U256,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[schema(value_type = String)]
+	gas_price: Option<U256>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[schema(value_type = String)]
+	max_fee_per_gas: Option<U256>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[schema(value_type = String)]
+	max_priority_fee_per_gas: Option<U256>,
+	#[schema(value_type = String)]
+	data: Bytes,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[schema(value_type = Vec<Object>)]
+	access_list: Option<AccessList>,
+}
+
+impl DecodedTx {
+	/// The first field of the transaction which isn't the requested one, fields left out of
+	/// the request are not compared.
+	pub(super) fn mismatch(&self, tx: &TransactionRequest) -> Option<&'static str> {
+		let differs = |requested: Option<U256>, signed: Option<U256>| {
+			requested.is_some_and(|requested| Some(requested) != signed)
+		};
+		let access_list = self.access_list.as_deref().unwrap_or_default();
+		if self.from != tx.from {
+			Some("from")
+		} else if differs(tx.nonce, Some(self.nonce)) {
+			Some("nonce")
+		} else if self.to != tx.to {
+			Some("to")
+		} else if self.value.wei() != tx.value.unwrap_or_default() {
+			Some("value")
+		} else if self.data != tx.data.clone().unwrap_or_default() {
+			Some("data")
+		} else if differs(tx.gas, Some(self.gas)) {
+			Some("gas")
+		} else if differs(tx.gas_price, self.gas_price) {
+			Some("gas_price")
+		} else if differs(tx.max_fee_per_gas, self.max_fee_per_gas) {
+			Some("max_fee_per_gas")
+		} else if differs(tx.max_priority_fee_per_gas, self.max_priority_fee_per_gas) {
+			Some("max_priority_fee_per_gas")
+		} else if tx.transaction_type.is_some_and(|t| t.as_u64() != self.transaction_type) {
+			Some("type")
+		} else if tx.access_list.as_deref().unwrap_or_default() != access_list {
+			Some("access_list")
+		} else {
+			None
+		}
+	}
+}
+
+/// Sign a transaction without asking the node, so the nonce, gas and fees must be given. The
+/// chain id is the network's unless it is given.
+pub(crate) async fn sign_offline(network: &Network, tx_request: TxRequest) -> Result<SignedTx> {
+	let (from, signer) = sender(
+		tx_request.from.as_deref(),
+		tx_request.signer.as_deref(),
+		tx_request.secret_key.as_deref(),
+	)?;
+	let signer =
+		signer.ok_or_else(|| InvalidParam("signer or secret_key is required".to_string()))?;
+
+	let mut tx = tx_request.transaction(network, from)?;
+	let eip1559 = tx.transaction_type == Some(2.into())
+		|| tx.max_fee_per_gas.is_some()
+		|| tx.max_priority_fee_per_gas.is_some();
+	let missing = [
+		("nonce", tx.nonce.is_none()),
+		("gas", tx.gas.is_none()),
+		("gas_price", !eip1559 && tx.gas_price.is_none()),
+		("max_fee_per_gas", eip1559 && tx.max_fee_per_gas.is_none()),
+		("max_priority_fee_per_gas", eip1559 && tx.max_priority_fee_per_gas.is_none()),
+	];
+	if let Some((name, _)) = missing.iter().find(|(_, missing)| *missing) {
+		return Err(InvalidParam(format!("{} is required to sign offline", name)));
+	}
+	if tx.transaction_type.is_none() {
+		tx.transaction_type = match (eip1559, &tx.access_list) {
+			(true, _) => Some(2.into()),
+			(false, Some(_)) => Some(1.into()),
+			(false, None) => None,
+		};
+	}
+
+	let raw = signer.sign_transaction(network, tx).await?;
+	let hash = keccak256(&raw.0).into();
+	info!("Signed transaction {:?} of {:?} offline", hash, from);
+	Ok(SignedTx { raw, hash })
+}
+
+/// Send a signed transaction once it decodes, belongs to the network and its nonce is unused.
+pub(crate) async fn broadcast(network: &Network, request: BroadcastRequest) -> Result<DecodedTx> {
+	let tx = decode(&request.raw.0)?;
+	match tx.chain_id {
+		None => return Err(InvalidParam("transaction is not replay protected".to_string())),
+		Some(chain_id) if U256::from(chain_id) != network.chain_id => {
+			return Err(InvalidParam(format!(
+				"chain id: {} doesn't match network {}, chain id: {}",
+				chain_id, network.name, network.chain_id
+			)))
+		}
+		_ => {}
+	}
+	let eth = network.web3.eth();
+	let used = eth.transaction_count(tx.from, Some(BlockNumber::Latest)).await?;
+	if tx.nonce < used {
+		return Err(InvalidParam(format!(
+			"nonce: {} of {:?} is used, the next one is {}",
+			tx.nonce, tx.from, used
+		)));
+	}
+
+	let hash = eth.send_raw_transaction(request.raw).await?;
+	info!("Broadcast transaction {:?} of {:?} on {}", hash, tx.from, network.name);
+	network.tracker.track(hash, tx.from, TxKind::Send, 0);
+	Ok(tx)
+}
+
+/// Decode a legacy, EIP-2930 or EIP-1559 transaction and recover its sender.
+pub(super) fn decode(raw: &[u8]) -> Result<DecodedTx> {
+	fn invalid(e: impl Display) -> crate::error::Error {
+		InvalidParam(format!("raw transaction is invalid, {}", e))
+	}
+
+	let (transaction_type, payload) = match raw.first() {
+		Some(&tx_type) if tx_type <= 0x7f => (tx_type as usize, &raw[1..]),
+		Some(_) => (0, raw),
+		None => return Err(invalid("it is empty")),
+	};
+	let fields = match transaction_type {
+		0 => 9,
+		1 => 11,
+		2 => 12,
+		tx_type => return Err(InvalidParam(format!("type: {} is not supported", tx_type))),
+	};
+	let rlp = Rlp::new(payload);
+	if !rlp.is_list() || rlp.item_count().map_err(invalid)? != fields {
+		return Err(invalid(format!(
+			"type {} needs a list of {} fields",
+			transaction_type, fields
+		)));
+	}
+
+	// Typed transactions start with the chain id, EIP-1559 fees take the place of the gas price.
+	let typed = transaction_type > 0;
+	let nonce_at = typed as usize;
+	let gas_at = nonce_at + if transaction_type == 2 { 3 } else { 2 };
+	let signature_at = fields - 3;
+	let to = rlp.at(gas_at + 1).map_err(invalid)?;
+	let access_list = match typed {
+		true => Some(
+			rlp.at(gas_at + 4)
+				.map_err(invalid)?
+				.iter()
+				.map(|item| {
+					Ok(AccessListItem { address: item.val_at(0)?, storage_keys: item.list_at(1)? })
+				})
+				.collect::<Result<AccessList, rlp::DecoderError>>()
+				.map_err(invalid)?,
+		),
+		false => None,
+	};
+	let v: u64 = rlp.val_at(signature_at).map_err(invalid)?;
+	let (chain_id, recovery_id) = match typed {
+		true => (Some(rlp.val_at(0).map_err(invalid)?), v),
+		false if v >= 35 => (Some((v - 35) / 2), (v - 35) % 2),
+		false => (None, v.wrapping_sub(27)),
+	};
+
+	// The signed message is the encoding without the signature, legacy transactions with
+	// replay protection append the chain id and two zeros instead.
+	let replay_protected = !typed && chain_id.is_some();
+	let mut unsigned = RlpStream::new_list(signature_at + if replay_protected { 3 } else { 0 });
+	for index in 0..signature_at {
+		unsigned.append_raw(rlp.at(index).map_err(invalid)?.as_raw(), 1);
+	}
+	if replay_protected {
+		unsigned.append(&chain_id.unwrap_or_default()).append(&0u8).append(&0u8);
+	}
+	let prefix = &raw[..raw.len() - payload.len()];
+	let message = keccak256(&[prefix, &unsigned.out()].concat());
+
+	let mut signature = [0u8; 64];
+	rlp.val_at::<U256>(signature_at + 1).map_err(invalid)?.to_big_endian(&mut signature[..32]);
+	rlp.val_at::<U256>(signature_at + 2).map_err(invalid)?.to_big_endian(&mut signature[32..]);
+	if recovery_id > 1 {
+		return Err(invalid(format!("v: {}", v)));
+	}
+	let from = recover(&message, &signature, recovery_id as i32).map_err(invalid)?;
+
+	let fee = |index: usize| rlp.val_at::<U256>(nonce_at + index).map_err(invalid);
+	let (gas_price, max_priority_fee_per_gas, max_fee_per_gas) = match transaction_type {
+		2 => (None, Some(fee(1)?), Some(fee(2)?)),
+		_ => (Some(fee(1)?), None, None),
+	};
+	Ok(DecodedTx {
+		hash: keccak256(raw).into(),
+		transaction_type: transaction_type as u64,
+		chain_id,
+		from,
+		nonce: rlp.val_at(nonce_at).map_err(invalid)?,
+		to: match to.is_empty() {
+			true => None,
+			false => Some(to.as_val().map_err(invalid)?),
+		},
+		value: rlp.val_at::<U256>(gas_at + 2).map_err(invalid)?.into(),
+		gas: rlp.val_at(gas_at).map_err(invalid)?,
+		gas_price,
+		max_fee_per_gas,
+		max_priority_fee_per_gas,
+		data: rlp.val_at::<Vec<u8>>(gas_at + 3).map_err(invalid)?.into(),
+		access_list,
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	use futures::executor::block_on;
+	use web3::{
+		signing::{Key, SecretKeyRef},
+		transports::Http,
+		types::{AccessListItem, TransactionParameters, H160},
+		Web3,
+	};
+
+	use super::decode;
+	use crate::ethereum::transaction::parse_secret_key;
+
+	#[test]
+	fn test_decode_signed() {
+		let key =
+			parse_secret_key("4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318")
+				.unwrap();
+		// Nothing is asked from the node when every field is given.
+		let accounts = Web3::new(Http::new("http://127.0.0.1:1").unwrap()).accounts();
+		let to = Some(H160::repeat_byte(0x11));
+		let access_list = vec![AccessListItem { address: H160::zero(), storage_keys: vec![] }];
+
+		for tx_type in 0..3u64 {
+			let parameters = TransactionParameters {
+				nonce: Some(7.into()),
+				to,
+				gas: 21000.into(),
+				gas_price: Some(1_000_000_000u64.into()),
+				value: 5.into(),
+				data: vec![1, 2, 3].into(),
+				chain_id: Some(1337),
+				transaction_type: Some(tx_type.into()),
+				access_list: (tx_type > 0).then(|| access_list.clone()),
+				max_fee_per_gas: Some(3_000_000_000u64.into()),
+				max_priority_fee_per_gas: Some(2.into()),
+			};
+			let signed = block_on(accounts.sign_transaction(parameters, &key)).unwrap();
+			let tx = decode(&signed.raw_transaction.0).unwrap();
+			assert_eq!(tx.hash, signed.transaction_hash);
+			assert_eq!(tx.transaction_type, tx_type);
+			assert_eq!(tx.chain_id, Some(1337));
+			assert_eq!(tx.from, SecretKeyRef::new(&key).address());
+			assert_eq!((tx.nonce, tx.to, tx.gas), (7.into(), to, 21000.into()));
+			assert_eq!(tx.data.0, vec![1, 2, 3]);
+			assert_eq!(tx.max_priority_fee_per_gas, (tx_type == 2).then(|| 2.into()));
+			assert_eq!(tx.access_list.map(|list| list.len()), (tx_type > 0).then_some(1));
+		}
+		assert!(decode(&[0xc0]).is_err());
+		assert!(decode(&[0x03, 0xc0]).is_err());
+	}
+}
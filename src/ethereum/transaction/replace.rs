@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+
+use log::info;
+use serde::Deserialize;
+use serde_json::json;
+use utoipa::ToSchema;
+use web3::{
+	types::{Transaction, TransactionRequest, H160, H256, U256},
+	Transport,
+};
+
+use crate::{
+	error::Error::{AnyError, Conflict, InvalidParam, NotFound},
+	Result,
+};
+
+use super::{
+	de_quantity, resolve_signer, sign_and_send, suggest_fees, tracker::TxInfo, Network,
+	TRANSFER_GAS,
+};
+
+/// Fee increase nodes require of a transaction replacing one with the same nonce, the default
+/// `txpool.pricebump` of geth.
+const REPLACEMENT_BUMP_PERCENT: u64 = 10;
+
+/// Fees of a replacement, left out they are the minimum bump over the original or the current
+/// suggestion, whichever is higher.
+#[derive(Debug, Default, Deserialize, ToSchema)]
+pub(crate) struct ReplaceTxRequest {
+	/// Alias or address of the signer of the sender.
+	pub signer: Option<String>,
+	/// Key of the sender, node accounts are sent from by the node when neither is given.
+	pub secret_key: Option<String>,
+	#[serde(default, deserialize_with = "de_quantity")]
+	#[schema(value_type = String)]
+	pub gas_price: Option<U256>,
+	#[serde(default, deserialize_with = "de_quantity")]
+	#[schema(value_type = String)]
+	pub max_fee_per_gas: Option<U256>,
+	#[serde(default, deserialize_with = "de_quantity")]
+	#[schema(value_type = String)]
+	pub max_priority_fee_per_gas: Option<U256>,
+}
+
+/// Replace a pending transaction by one with the same nonce and higher fees. A speed-up sends
+/// the same transaction again while a cancellation sends nothing to the sender itself.
+pub(crate) async fn replace_transaction(
+	network: &Network,
+	hash: H256,
+	request: ReplaceTxRequest,
+	cancel: bool,
+) -> Result<TxInfo> {
+	let eth = network.web3.eth();
+	let original = eth
+		.transaction(hash.into())
+		.await?
+		.ok_or_else(|| NotFound(format!("transaction: {:?}", hash)))?;
+	if original.block_number.is_some() {
+		return Err(Conflict(format!("transaction: {:?} is already mined", hash)));
+	}
+	let from = original.from.ok_or_else(|| {
+		AnyError(anyhow::anyhow!("node reports no sender of transaction {:?}", hash))
+	})?;
+	let signer =
+		resolve_signer(request.signer.as_deref(), request.secret_key.as_deref(), Some(from))?;
+	if signer.as_ref().is_some_and(|signer| signer.address() != from) {
+		return Err(InvalidParam(format!("signer is not the one of {:?}", from)));
+	}
+
+	let mut tx = TransactionRequest { from, nonce: Some(original.nonce), ..Default::default() };
+	if cancel {
+		tx.to = Some(from);
+		tx.gas = Some(TRANSFER_GAS.into());
+		tx.value = Some(U256::zero());
+	} else {
+		tx.to = original.to;
+		tx.gas = Some(original.gas);
+		tx.value = Some(original.value);
+		tx.data = Some(original.input.clone());
+		tx.access_list = original.access_list.clone();
+	}
+	fill_replacement_fees(network, &original, &request, &mut tx).await?;
+
+	let replacement = match &signer {
+		Some(signer) => sign_and_send(network, tx, signer.as_ref()).await?,
+		None => eth.send_transaction(tx).await?,
+	};
+	let action = if cancel { "Cancel" } else { "Speed up" };
+	info!("{} transaction {:?} on {} by {:?}", action, hash, network.name, replacement);
+	network.tracker.track_replacement(hash, replacement, from, cancel);
+	network.tracker.status(network, replacement).await
+}
+
+/// The transaction of an account with the nonce, which the node keeps in its pool.
+pub(super) async fn pending_transaction(
+	network: &Network,
+	account: H160,
+	nonce: U256,
+) -> Result<Transaction> {
+	let content =
+		network.web3.transport().execute("txpool_contentFrom", vec![json!(account)]).await?;
+	// The pending and the queued transactions by their nonce in decimal.
+	let content: HashMap<String, HashMap<String, Transaction>> =
+		serde_json::from_value(content).map_err(|e| AnyError(e.into()))?;
+	content.into_values().find_map(|mut txs| txs.remove(&nonce.to_string())).ok_or_else(|| {
+		NotFound(format!("pending transaction of {:?} with nonce {}", account, nonce))
+	})
+}
+
+/// Type and fees of a replacement of `original`, the requested ones or the minimum bump over
+/// the original's or the current suggestion, whichever is higher.
+pub(super) async fn fill_replacement_fees(
+	network: &Network,
+	original: &Transaction,
+	request: &ReplaceTxRequest,
+	tx: &mut TransactionRequest,
+) -> Result<()> {
+	tx.transaction_type = original.transaction_type;
+	let eip1559 = original.max_fee_per_gas.is_some();
+	if eip1559 && request.gas_price.is_some() {
+		return Err(InvalidParam("gas_price can't replace an EIP-1559 transaction".to_string()));
+	}
+	if !eip1559 && (request.max_fee_per_gas.is_some() || request.max_priority_fee_per_gas.is_some())
+	{
+		return Err(InvalidParam(
+			"max_fee_per_gas and max_priority_fee_per_gas can't replace a legacy transaction"
+				.to_string(),
+		));
+	}
+	if eip1559 {
+		let original_fees = (
+			original.max_fee_per_gas.unwrap_or_default(),
+			original.max_priority_fee_per_gas.unwrap_or_default(),
+		);
+		let suggested = suggest_fees(network).await.unwrap_or_default();
+		let (max_fee, tip) = eip1559_fees(request, original_fees, suggested)?;
+		tx.max_fee_per_gas = Some(max_fee);
+		tx.max_priority_fee_per_gas = Some(tip);
+	} else {
+		let gas_price = network.web3.eth().gas_price().await?;
+		let min_gas_price = bump(original.gas_price.unwrap_or_default());
+		tx.gas_price =
+			Some(replacement_fee("gas_price", request.gas_price, min_gas_price, gas_price)?);
+	}
+	Ok(())
+}
+
+/// Lowest fee a replacement may pay, rounded up.
+fn bump(fee: U256) -> U256 {
+	let bumped = fee.saturating_mul((100 + REPLACEMENT_BUMP_PERCENT).into());
+	(bumped + 99) / 100
+}
+
+/// Max fee and tip of an EIP-1559 replacement from the ones of the original and the suggested
+/// base fee and tip.
+fn eip1559_fees(
+	request: &ReplaceTxRequest,
+	(original_max_fee, original_tip): (U256, U256),
+	(base_fee, suggested_tip): (U256, U256),
+) -> Result<(U256, U256)> {
+	let min_tip = bump(original_tip);
+	let tip = replacement_fee(
+		"max_priority_fee_per_gas",
+		request.max_priority_fee_per_gas,
+		min_tip,
+		suggested_tip,
+	)?;
+	let max_fee = replacement_fee(
+		"max_fee_per_gas",
+		request.max_fee_per_gas,
+		bump(original_max_fee),
+		base_fee.saturating_mul(2.into()) + tip,
+	)?;
+	// The tip is capped by the max fee, but not below the bump the node requires.
+	if max_fee < min_tip {
+		return Err(InvalidParam(format!(
+			"max_fee_per_gas: {} is below the minimum max_priority_fee_per_gas {}",
+			max_fee, min_tip
+		)));
+	}
+	Ok((max_fee, tip.min(max_fee)))
+}
+
+/// The requested fee if it is high enough, otherwise the minimum or the suggested fee.
+fn replacement_fee(
+	name: &str,
+	requested: Option<U256>,
+	minimum: U256,
+	suggested: U256,
+) -> Result<U256> {
+	match requested {
+		Some(fee) if fee < minimum => Err(InvalidParam(format!(
+			"{}: {} is below the replacement minimum {}",
+			name, fee, minimum
+		))),
+		Some(fee) => Ok(fee),
+		None => Ok(minimum.max(suggested)),
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use web3::types::U256;
+
+	use super::{bump, eip1559_fees, replacement_fee, ReplaceTxRequest};
+
+	#[test]
+	fn test_replacement_fee() {
+		assert_eq!(bump(1_000_000_000u64.into()), 1_100_000_000u64.into());
+		assert_eq!(bump(15.into()), 17.into());
+		assert_eq!(bump(U256::zero()), U256::zero());
+
+		let fee = |requested: Option<u64>| {
+			replacement_fee("gas_price", requested.map(U256::from), 110.into(), 120.into()).ok()
+		};
+		assert_eq!(fee(None), Some(120.into()));
+		assert_eq!(fee(Some(110)), Some(110.into()));
+		assert_eq!(fee(Some(109)), None);
+	}
+
+	#[test]
+	fn test_eip1559_fees() {
+		let fees = |max_fee: Option<u64>, tip: Option<u64>| {
+			let request = ReplaceTxRequest {
+				max_fee_per_gas: max_fee.map(U256::from),
+				max_priority_fee_per_gas: tip.map(U256::from),
+				..Default::default()
+			};
+			eip1559_fees(&request, (100.into(), 10.into()), (40.into(), 5.into()))
+				.map(|(max_fee, tip)| (max_fee.as_u64(), tip.as_u64()))
+				.ok()
+		};
+		assert_eq!(fees(None, None), Some((110, 11)));
+		assert_eq!(fees(Some(200), Some(50)), Some((200, 50)));
+		// The tip is capped by a lower max fee, as long as it stays above the bump.
+		assert_eq!(fees(Some(110), Some(150)), Some((110, 110)));
+		assert_eq!(fees(Some(109), None), None);
+		assert_eq!(fees(None, Some(10)), None);
+	}
+}
@@ -0,0 +1,251 @@
+use futures::{future::BoxFuture, FutureExt};
+use log::info;
+use secp256k1::SecretKey;
+use serde_json::{json, Value as JsonValue};
+use web3::{
+	signing::{Key, SecretKeyRef},
+	transports::Http,
+	types::{Bytes, TransactionParameters, TransactionRequest, H160},
+	Transport, Web3,
+};
+
+use super::{offline::decode, Network};
+use crate::{
+	error::Error::{AnyError, ConfigError, InvalidParam},
+	Result,
+};
+
+/// Signs the transactions of one account, they come with the nonce, gas and fees filled in.
+pub(crate) trait Signer: Send + Sync {
+	/// The account the transactions are sent from.
+	fn address(&self) -> H160;
+
+	/// Sign a transaction for the chain of the network, the result is its raw encoding.
+	fn sign_transaction<'a>(
+		&'a self,
+		network: &'a Network,
+		tx: TransactionRequest,
+	) -> BoxFuture<'a, Result<Bytes>>;
+
+	/// The key when this service holds it, messages can only be signed with such keys.
+	fn secret_key(&self) -> Option<&SecretKey>;
+}
+
+/// A key given in the request or derived by the wallet.
+pub(crate) struct LocalSigner(SecretKey);
+
+impl LocalSigner {
+	pub(crate) fn new(key: SecretKey) -> LocalSigner {
+		LocalSigner(key)
+	}
+}
+
+impl Signer for LocalSigner {
+	fn address(&self) -> H160 {
+		SecretKeyRef::new(&self.0).address()
+	}
+
+	fn sign_transaction<'a>(
+		&'a self,
+		network: &'a Network,
+		tx: TransactionRequest,
+	) -> BoxFuture<'a, Result<Bytes>> {
+		sign_with_key(network, tx, &self.0).boxed()
+	}
+
+	fn secret_key(&self) -> Option<&SecretKey> {
+		Some(&self.0)
+	}
+}
+
+/// Sign a transaction with a key held by this service, nothing is asked from the node.
+pub(crate) async fn sign_with_key(
+	network: &Network,
+	tx: TransactionRequest,
+	key: &SecretKey,
+) -> Result<Bytes> {
+	let parameters = TransactionParameters {
+		nonce: tx.nonce,
+		to: tx.to,
+		gas: tx.gas.unwrap_or_default(),
+		gas_price: tx.gas_price,
+		value: tx.value.unwrap_or_default(),
+		data: tx.data.unwrap_or_default(),
+		chain_id: Some(network.chain_id.as_u64()),
+		transaction_type: tx.transaction_type,
+		access_list: tx.access_list,
+		max_fee_per_gas: tx.max_fee_per_gas,
+		max_priority_fee_per_gas: tx.max_priority_fee_per_gas,
+	};
+	let signed = network.web3.accounts().sign_transaction(parameters, key).await?;
+	Ok(signed.raw_transaction)
+}
+
+/// A signer in a separate process, like Web3Signer or Clef, which keeps the keys of its accounts
+/// and signs over JSON-RPC.
+#[derive(Debug)]
+pub(crate) struct RemoteSigners {
+	transport: Http,
+	accounts: Vec<H160>,
+}
+
+/// An account of the remote signer.
+#[derive(Debug)]
+pub(crate) struct RemoteSigner {
+	transport: Http,
+	address: H160,
+}
+
+impl RemoteSigners {
+	/// Connect to the signer at `url`, its accounts are asked by `eth_accounts` unless given.
+	pub(crate) async fn connect(url: &str, accounts: &[H160]) -> Result<RemoteSigners> {
+		let transport = Http::new(url)
+			.map_err(|e| ConfigError(format!("remote signer url: {} is invalid, {}", url, e)))?;
+		let accounts = match accounts {
+			[] => Web3::new(transport.clone()).eth().accounts().await.map_err(|e| {
+				ConfigError(format!("list accounts of remote signer {} failed, {}", url, e))
+			})?,
+			accounts => accounts.to_vec(),
+		};
+
+		info!("Remote signer {} holds {} accounts", url, accounts.len());
+		Ok(RemoteSigners { transport, accounts })
+	}
+
+	pub(crate) fn signer(&self, address: H160) -> Option<RemoteSigner> {
+		self.accounts
+			.contains(&address)
+			.then(|| RemoteSigner { transport: self.transport.clone(), address })
+	}
+}
+
+impl RemoteSigner {
+	/// Ask for the signature by `eth_signTransaction`, and make sure the answer is the
+	/// requested transaction of this account.
+	async fn sign(&self, network: &Network, tx: TransactionRequest) -> Result<Bytes> {
+		let mut request = serde_json::to_value(&tx).map_err(|e| AnyError(e.into()))?;
+		request["chainId"] = json!(network.chain_id);
+		let result = self.transport.execute("eth_signTransaction", vec![request]).await?;
+
+		// Web3Signer answers the raw transaction, geth and Clef an object holding it in `raw`.
+		let raw = match result {
+			JsonValue::Object(mut result) => result.remove("raw").unwrap_or_default(),
+			raw => raw,
+		};
+		let raw: Bytes = serde_json::from_value(raw)
+			.map_err(|e| AnyError(anyhow::anyhow!("remote signer answered no raw tx, {}", e)))?;
+		let signed = decode(&raw.0).map_err(|e| match e {
+			InvalidParam(e) => AnyError(anyhow::anyhow!("answer of the remote signer: {}", e)),
+			e => e,
+		})?;
+		let mismatch = match signed.chain_id != Some(network.chain_id.as_u64()) {
+			true => Some("chain_id"),
+			false => signed.mismatch(&TransactionRequest { from: self.address, ..tx }),
+		};
+		if let Some(field) = mismatch {
+			return Err(AnyError(anyhow::anyhow!(
+				"remote signer answered another {} than requested for {:?}",
+				field,
+				self.address
+			)));
+		}
+		Ok(raw)
+	}
+}
+
+impl Signer for RemoteSigner {
+	fn address(&self) -> H160 {
+		self.address
+	}
+
+	fn sign_transaction<'a>(
+		&'a self,
+		network: &'a Network,
+		tx: TransactionRequest,
+	) -> BoxFuture<'a, Result<Bytes>> {
+		self.sign(network, tx).boxed()
+	}
+
+	fn secret_key(&self) -> Option<&SecretKey> {
+		None
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::sync::Arc;
+
+	use serde_json::{json, Value as JsonValue};
+	use web3::{
+		signing::{Key, SecretKeyRef},
+		types::{TransactionRequest, H160},
+	};
+
+	use super::{sign_with_key, RemoteSigners, Signer};
+	use crate::ethereum::{
+		testing,
+		transaction::{offline::decode, parse_secret_key},
+		Network,
+	};
+
+	/// A stand-in remote signer holding one key, it answers like Web3Signer.
+	async fn serve_signer(network: Arc<Network>) -> String {
+		testing::serve(move |request: JsonValue| {
+			let network = network.clone();
+			async move {
+				let key = parse_secret_key(
+					"4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318",
+				)
+				.unwrap();
+				Ok(match request["method"].as_str() {
+					Some("eth_accounts") => json!([SecretKeyRef::new(&key).address()]),
+					_ => {
+						let mut tx: TransactionRequest =
+							serde_json::from_value(request["params"][0].clone()).unwrap();
+						// A rogue signer, it raises the value of contract calls.
+						if tx.data.is_some() {
+							tx.value = Some(tx.value.unwrap_or_default() + 1);
+						}
+						json!(sign_with_key(&network, tx, &key).await.unwrap())
+					}
+				})
+			}
+		})
+		.await
+	}
+
+	#[tokio::test]
+	async fn test_remote_signer() {
+		// Signing with every field filled in asks nothing from the node.
+		let network = Arc::new(testing::network("http://127.0.0.1:1"));
+		let url = serve_signer(network.clone()).await;
+		let address = "0x2c7536e3605d9c16a7a3d7b1898e529396a65c23".parse::<H160>().unwrap();
+		let tx = |from: H160| TransactionRequest {
+			from,
+			to: Some(H160::repeat_byte(0x11)),
+			gas: Some(21000.into()),
+			max_fee_per_gas: Some(2_000_000_000u64.into()),
+			max_priority_fee_per_gas: Some(1_000_000_000u64.into()),
+			value: Some(5.into()),
+			nonce: Some(3.into()),
+			transaction_type: Some(2.into()),
+			..Default::default()
+		};
+
+		let remote = RemoteSigners::connect(&url, &[]).await.unwrap();
+		assert!(remote.signer(H160::zero()).is_none());
+		let signer = remote.signer(address).unwrap();
+		assert!(signer.secret_key().is_none());
+		let raw = signer.sign_transaction(&network, tx(address)).await.unwrap();
+		let signed = decode(&raw.0).unwrap();
+		assert_eq!((signed.from, signed.nonce), (address, 3.into()));
+		let call = TransactionRequest { data: Some(vec![1].into()), ..tx(address) };
+		let err = signer.sign_transaction(&network, call).await.unwrap_err();
+		assert!(err.to_string().contains("another value"), "{}", err);
+
+		// The stand-in signs with another key than the one of the account.
+		let remote = RemoteSigners::connect(&url, &[H160::zero()]).await.unwrap();
+		let signer = remote.signer(H160::zero()).unwrap();
+		assert!(signer.sign_transaction(&network, tx(H160::zero())).await.is_err());
+	}
+}
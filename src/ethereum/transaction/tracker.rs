@@ -0,0 +1,496 @@
+use std::{
+	collections::HashMap,
+	sync::Mutex,
+	time::{Duration, Instant},
+};
+
+use log::{info, warn};
+use serde::Serialize;
+use serde_json::{json, Value as JsonValue};
+use utoipa::ToSchema;
+use web3::types::{BlockId, BlockNumber, CallRequest, Log, TransactionReceipt, H160, H256};
+
+use crate::{
+	contracts::{registry, to_checksum},
+	error::Error::{self, NotFound},
+	ethereum::{
+		amount::Amount,
+		deployment::{self, Deployment},
+	},
+	Result,
+};
+
+use super::Network;
+
+/// How often the receipts of the tracked transactions are polled.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+/// A transaction the node doesn't know of for this long is dropped.
+const DROP_AFTER: Duration = Duration::from_secs(120);
+/// Finished transactions are kept this long for lookups.
+const RETAIN: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub(crate) enum TxStatus {
+	/// Known to the node but not mined.
+	Pending,
+	/// Mined with fewer confirmations than required.
+	Mined,
+	Confirmed,
+	/// Mined but reverted.
+	Failed,
+	/// Neither mined nor known to the node anymore.
+	Dropped,
+	/// Taken over by a transaction with the same nonce.
+	Replaced,
+}
+
+impl TxStatus {
+	fn is_final(self) -> bool {
+		matches!(
+			self,
+			TxStatus::Confirmed | TxStatus::Failed | TxStatus::Dropped | TxStatus::Replaced
+		)
+	}
+}
+
+/// What a tracked transaction does, pending deployments are confirmed or discarded with it.
+#[derive(Debug, Clone)]
+pub(crate) enum TxKind {
+	Send,
+	Call { contract_name: String },
+	Deploy { contract_name: String, address: H160 },
+}
+
+impl TxKind {
+	fn contract_name(&self) -> Option<&str> {
+		match self {
+			TxKind::Send => None,
+			TxKind::Call { contract_name } | TxKind::Deploy { contract_name, .. } => {
+				Some(contract_name)
+			}
+		}
+	}
+}
+
+/// Transactions submitted by this service, followed by a background task until they are
+/// confirmed, failed or dropped.
+#[derive(Debug, Default)]
+pub(crate) struct Tracker {
+	txs: Mutex<HashMap<H256, TrackedTx>>,
+}
+
+#[derive(Debug)]
+struct TrackedTx {
+	from: H160,
+	kind: TxKind,
+	/// Blocks required on top of the one the transaction is mined in.
+	required: usize,
+	status: TxStatus,
+	confirmations: u64,
+	receipt: Option<TransactionReceipt>,
+	deployment: Option<Deployment>,
+	error: Option<String>,
+	replaces: Option<H256>,
+	replaced_by: Option<H256>,
+	/// Last time the node knew of the transaction.
+	seen: Instant,
+	finished: Option<Instant>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct TxInfo {
+	#[schema(value_type = String)]
+	pub hash: H256,
+	status: TxStatus,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	contract_name: Option<String>,
+	/// Blocks mined on top of the one of the transaction.
+	confirmations: u64,
+	required_confirmations: usize,
+	block_number: Option<u64>,
+	gas_used: Option<u64>,
+	/// Price per gas in wei.
+	#[schema(value_type = Option<String>)]
+	effective_gas_price: Option<Amount>,
+	/// Gas used times the effective gas price, in wei.
+	#[schema(value_type = Option<String>)]
+	fee: Option<Amount>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	deployment: Option<Deployment>,
+	/// Logs decoded by the events of the emitting contract, raw when it isn't known.
+	logs: Vec<JsonValue>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	error: Option<String>,
+	/// The transaction this one sped up or cancelled.
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[schema(value_type = Option<String>)]
+	replaces: Option<H256>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	#[schema(value_type = Option<String>)]
+	replaced_by: Option<H256>,
+}
+
+impl Tracker {
+	/// Follow a submitted transaction, `required` confirmations make it confirmed.
+	pub(crate) fn track(&self, hash: H256, from: H160, kind: TxKind, required: usize) {
+		self.txs.lock().unwrap().insert(hash, TrackedTx::new(from, kind, required));
+	}
+
+	/// Show the pending deployment of a tracked deployment transaction.
+	pub(crate) fn set_deployment(&self, hash: H256, deployment: Deployment) {
+		if let Some(tx) = self.txs.lock().unwrap().get_mut(&hash) {
+			tx.deployment = Some(deployment);
+		}
+	}
+
+	/// Pick up the deployments which were pending before a restart, the ones which never got a
+	/// transaction are dropped.
+	fn resume(&self, network: &Network) -> Result<()> {
+		for pending in deployment::pending(network)? {
+			// Both were written by the book.
+			let address = pending.address.parse().unwrap();
+			let Ok(hash) = pending.tx_hash.parse::<H256>() else {
+				deployment::discard(network, address)?;
+				continue;
+			};
+			let kind = TxKind::Deploy { contract_name: pending.contract_name.clone(), address };
+			self.track(hash, pending.deployer.parse().unwrap(), kind, 0);
+			self.set_deployment(hash, pending);
+		}
+		Ok(())
+	}
+
+	/// Follow a transaction which replaces another one, a speed-up takes over what the
+	/// original does while a cancellation is a plain transfer.
+	pub(crate) fn track_replacement(&self, original: H256, hash: H256, from: H160, cancel: bool) {
+		let mut txs = self.txs.lock().unwrap();
+		let (kind, required) = match txs.get_mut(&original) {
+			Some(tx) => {
+				tx.replaced_by = Some(hash);
+				(if cancel { TxKind::Send } else { tx.kind.clone() }, tx.required)
+			}
+			None => (TxKind::Send, 0),
+		};
+		let mut tx = TrackedTx::new(from, kind, required);
+		tx.replaces = Some(original);
+		txs.insert(hash, tx);
+	}
+
+	/// Status of a transaction, those which weren't submitted here are looked up on the node.
+	pub(crate) async fn status(&self, network: &Network, hash: H256) -> Result<TxInfo> {
+		if let Some(tx) = self.txs.lock().unwrap().get(&hash) {
+			return Ok(tx.info(network, hash));
+		}
+
+		let eth = network.web3.eth();
+		let (receipt, block) = match eth.transaction_receipt(hash).await? {
+			Some(receipt) => (Some(receipt), eth.block_number().await?.as_u64()),
+			None if eth.transaction(hash.into()).await?.is_some() => (None, 0),
+			None => return Err(NotFound(format!("transaction: {:?}", hash))),
+		};
+		let from = receipt.as_ref().map(|receipt| receipt.from).unwrap_or_default();
+		let mut tx = TrackedTx::new(from, TxKind::Send, 0);
+		tx.update(receipt, block);
+		Ok(tx.info(network, hash))
+	}
+
+	/// Wait until a transaction is confirmed, failed or dropped.
+	pub(crate) async fn wait(&self, network: &Network, hash: H256) -> Result<TxInfo> {
+		loop {
+			let info = self.status(network, hash).await?;
+			if info.status.is_final() {
+				return Ok(info);
+			}
+			tokio::time::sleep(POLL_INTERVAL).await;
+		}
+	}
+
+	async fn poll(&self, network: &Network) -> Result<()> {
+		let now = Instant::now();
+		let hashes = {
+			let mut txs = self.txs.lock().unwrap();
+			txs.retain(|_, tx| tx.finished.is_none_or(|finished| now - finished < RETAIN));
+			txs.iter()
+				.filter(|(_, tx)| !tx.status.is_final())
+				.map(|(hash, _)| *hash)
+				.collect::<Vec<_>>()
+		};
+		if hashes.is_empty() {
+			return Ok(());
+		}
+
+		let eth = network.web3.eth();
+		let block = eth.block_number().await?.as_u64();
+		let mut reverted = vec![];
+		for hash in hashes {
+			// One failing lookup waits for the next round, the others go on.
+			let lookup = async {
+				let receipt = eth.transaction_receipt(hash).await?;
+				let known = receipt.is_some() || eth.transaction(hash.into()).await?.is_some();
+				Ok::<_, web3::Error>((receipt, known))
+			};
+			let (receipt, known) = match lookup.await {
+				Ok(lookup) => lookup,
+				Err(e) => {
+					warn!("Look up transaction {:?} on {} failed, {}", hash, network.name, e);
+					continue;
+				}
+			};
+
+			let mut txs = self.txs.lock().unwrap();
+			// A cancellation takes the deployment of the original with it.
+			let cancelled = txs
+				.get(&hash)
+				.and_then(|tx| tx.replaced_by)
+				.and_then(|replacement| txs.get(&replacement))
+				.is_some_and(|replacement| matches!(replacement.kind, TxKind::Send));
+			let Some(tx) = txs.get_mut(&hash) else { continue };
+			if known {
+				tx.seen = now;
+			} else if let Some(replacement) = tx.replaced_by {
+				tx.status = TxStatus::Replaced;
+				tx.finished = Some(now);
+				if cancelled {
+					tx.discard_deployment(network);
+				}
+				info!(
+					"Transaction {:?} on {} is replaced by {:?}",
+					hash, network.name, replacement
+				);
+				continue;
+			} else if now - tx.seen > DROP_AFTER {
+				tx.status = TxStatus::Dropped;
+				tx.finished = Some(now);
+				tx.discard_deployment(network);
+				warn!("Transaction {:?} on {} is dropped", hash, network.name);
+				continue;
+			}
+			tx.update(receipt, block);
+			match tx.status {
+				TxStatus::Confirmed => tx.confirm_deployment(network, hash),
+				TxStatus::Failed => tx.discard_deployment(network),
+				_ => {}
+			}
+			if tx.status.is_final() {
+				tx.finished = Some(now);
+				info!("Transaction {:?} on {} is {:?}", hash, network.name, tx.status);
+			}
+			if let (TxStatus::Failed, Some(contract_name)) = (tx.status, tx.kind.contract_name()) {
+				reverted.push((hash, contract_name.to_string()));
+			}
+		}
+
+		for (hash, contract_name) in reverted {
+			let error = revert_reason(network, hash, &contract_name).await;
+			if let Some(tx) = self.txs.lock().unwrap().get_mut(&hash) {
+				tx.error = Some(error);
+			}
+		}
+		Ok(())
+	}
+}
+
+impl TrackedTx {
+	fn new(from: H160, kind: TxKind, required: usize) -> TrackedTx {
+		TrackedTx {
+			from,
+			kind,
+			required,
+			status: TxStatus::Pending,
+			confirmations: 0,
+			receipt: None,
+			deployment: None,
+			error: None,
+			replaces: None,
+			replaced_by: None,
+			seen: Instant::now(),
+			finished: None,
+		}
+	}
+
+	fn update(&mut self, receipt: Option<TransactionReceipt>, block: u64) {
+		let Some(receipt) = receipt else {
+			// Without a receipt a mined transaction was reorganized out.
+			self.status = TxStatus::Pending;
+			self.confirmations = 0;
+			self.receipt = None;
+			return;
+		};
+
+		let mined = receipt.block_number.map_or(block, |number| number.as_u64());
+		self.confirmations = block.saturating_sub(mined);
+		self.status = match receipt.status {
+			Some(status) if status.is_zero() => TxStatus::Failed,
+			_ if self.confirmations >= self.required as u64 => TxStatus::Confirmed,
+			_ => TxStatus::Mined,
+		};
+		self.receipt = Some(receipt);
+	}
+
+	fn confirm_deployment(&mut self, network: &Network, hash: H256) {
+		let TxKind::Deploy { contract_name, address } = &self.kind else {
+			return;
+		};
+		let block = self.receipt.as_ref().and_then(|receipt| receipt.block_number);
+		info!(
+			"Deploy contract {} on {}, account: {:?}, addr: {:?}",
+			contract_name, network.name, self.from, address
+		);
+		match deployment::confirm(network, *address, hash, block.unwrap_or_default().as_u64()) {
+			Ok(confirmed) => self.deployment = Some(confirmed),
+			Err(e) => {
+				warn!("Deployment {:?} isn't confirmed, {}", address, e);
+				self.error = Some(e.to_string());
+			}
+		}
+	}
+
+	fn discard_deployment(&mut self, network: &Network) {
+		let TxKind::Deploy { address, .. } = &self.kind else {
+			return;
+		};
+		if let Err(e) = deployment::discard(network, *address) {
+			warn!("Pending deployment {:?} isn't discarded, {}", address, e);
+		}
+		self.deployment = None;
+	}
+
+	fn info(&self, network: &Network, hash: H256) -> TxInfo {
+		let receipt = self.receipt.as_ref();
+		let gas_used = receipt.and_then(|receipt| receipt.gas_used);
+		let effective_gas_price = receipt.and_then(|receipt| receipt.effective_gas_price);
+		TxInfo {
+			hash,
+			status: self.status,
+			contract_name: self.kind.contract_name().map(str::to_string),
+			confirmations: self.confirmations,
+			required_confirmations: self.required,
+			block_number: receipt.and_then(|receipt| receipt.block_number).map(|n| n.as_u64()),
+			gas_used: gas_used.map(|gas| gas.as_u64()),
+			effective_gas_price: effective_gas_price.map(Amount::from),
+			fee: gas_used.zip(effective_gas_price).map(|(gas, price)| (gas * price).into()),
+			deployment: self.deployment.clone(),
+			logs: receipt
+				.map(|receipt| {
+					receipt.logs.iter().map(|log| self.decode_log(network, log)).collect()
+				})
+				.unwrap_or_default(),
+			error: self.error.clone(),
+			replaces: self.replaces,
+			replaced_by: self.replaced_by,
+		}
+	}
+
+	/// Logs of the called or deployed contract are decoded by its abi, others by the abi of
+	/// the deployment at their address.
+	fn decode_log(&self, network: &Network, log: &Log) -> JsonValue {
+		let address = to_checksum(&log.address);
+		let receipt = self.receipt.as_ref();
+		let own = receipt.is_some_and(|receipt| {
+			receipt.to == Some(log.address) || receipt.contract_address == Some(log.address)
+		});
+		let contract_name = match self.kind.contract_name() {
+			Some(contract_name) if own => Some(contract_name.to_string()),
+			_ => deployment::lookup(network, &address)
+				.ok()
+				.map(|deployment| deployment.contract_name),
+		};
+		let decoded = contract_name
+			.and_then(|name| registry::artifact(&name).ok())
+			.and_then(|artifact| artifact.abi.decode_log(&log.topics, &log.data.0));
+
+		match decoded {
+			Some(JsonValue::Object(mut decoded)) => {
+				decoded.insert("address".to_string(), address.into());
+				JsonValue::Object(decoded)
+			}
+			_ => json!({ "address": address, "topics": log.topics, "data": log.data }),
+		}
+	}
+}
+
+/// Replay a reverted contract transaction by `eth_call` at the block it was mined in, to
+/// decode why it reverted.
+async fn revert_reason(network: &Network, hash: H256, contract_name: &str) -> String {
+	let eth = network.web3.eth();
+	let tx = match eth.transaction(hash.into()).await {
+		Ok(Some(tx)) => tx,
+		Ok(None) => return "execution reverted".to_string(),
+		Err(e) => return format!("execution reverted, replay failed: {}", e),
+	};
+	let call = CallRequest {
+		from: tx.from,
+		to: tx.to,
+		gas: Some(tx.gas),
+		value: Some(tx.value),
+		data: Some(tx.input),
+		..Default::default()
+	};
+	let block = tx.block_number.map(|number| BlockId::Number(BlockNumber::Number(number)));
+	let Err(e) = eth.call(call, block).await else {
+		return "execution reverted".to_string();
+	};
+	match registry::artifact(contract_name) {
+		Ok(artifact) => Error::from(e).decode_revert(&artifact.abi).to_string(),
+		Err(_) => Error::from(e).to_string(),
+	}
+}
+
+/// Poll the transactions of a network in the background.
+pub(crate) async fn run(network: &'static Network) {
+	if let Err(e) = network.tracker.resume(network) {
+		warn!("Resume pending deployments on {} failed, {}", network.name, e);
+	}
+	let mut interval = tokio::time::interval(POLL_INTERVAL);
+	loop {
+		interval.tick().await;
+		if let Err(e) = network.tracker.poll(network).await {
+			warn!("Tracking transactions on {} failed, {}", network.name, e);
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use web3::types::{TransactionReceipt, H160};
+
+	use super::{TrackedTx, TxKind, TxStatus};
+
+	fn receipt(block: u64, status: u64) -> Option<TransactionReceipt> {
+		Some(TransactionReceipt {
+			block_number: Some(block.into()),
+			status: Some(status.into()),
+			..Default::default()
+		})
+	}
+
+	#[test]
+	fn test_update() {
+		let mut tx = TrackedTx::new(H160::zero(), TxKind::Send, 2);
+		tx.update(None, 10);
+		assert_eq!((tx.status, tx.confirmations), (TxStatus::Pending, 0));
+
+		tx.update(receipt(10, 1), 11);
+		assert_eq!((tx.status, tx.confirmations), (TxStatus::Mined, 1));
+		assert!(tx.receipt.is_some());
+
+		// Reorganized out, then mined again in another block.
+		tx.update(None, 12);
+		assert_eq!((tx.status, tx.confirmations), (TxStatus::Pending, 0));
+		assert!(tx.receipt.is_none());
+		tx.update(receipt(12, 1), 14);
+		assert_eq!((tx.status, tx.confirmations), (TxStatus::Confirmed, 2));
+		assert!(tx.status.is_final());
+
+		let mut tx = TrackedTx::new(H160::zero(), TxKind::Send, 2);
+		tx.update(receipt(10, 0), 10);
+		assert_eq!(tx.status, TxStatus::Failed);
+		assert!(tx.status.is_final());
+
+		// Without confirmations required mined is confirmed.
+		let mut tx = TrackedTx::new(H160::zero(), TxKind::Send, 0);
+		tx.update(receipt(10, 1), 10);
+		assert_eq!(tx.status, TxStatus::Confirmed);
+	}
+}
@@ -0,0 +1,286 @@
+use std::{
+	path::PathBuf,
+	str::FromStr,
+	sync::{
+		atomic::{AtomicUsize, Ordering},
+		Arc, RwLock,
+	},
+	time::Duration,
+};
+
+use futures::{future::BoxFuture, stream::BoxStream, FutureExt, StreamExt};
+use jsonrpc_core::Call;
+use log::{info, warn};
+use serde_json::Value;
+use web3::{
+	api::SubscriptionId,
+	error::{Error as Web3Error, TransportError},
+	helpers,
+	transports::{Http, Ipc, WebSocket},
+	DuplexTransport, RequestId, Transport,
+};
+
+use super::pool::Pool;
+use crate::{config::NetworkConfig, error::Error::ConfigError};
+
+pub(crate) const RPC_TIMEOUT_MSG: &str = "rpc request timed out";
+
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+
+/// Where the node listens, picked by the scheme of `rpc_url`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) enum Endpoint {
+	Http(String),
+	Ws(String),
+	Ipc(PathBuf),
+}
+
+impl FromStr for Endpoint {
+	type Err = crate::error::Error;
+
+	fn from_str(url: &str) -> Result<Self, Self::Err> {
+		match url.split_once("://") {
+			Some(("http" | "https", _)) => Ok(Endpoint::Http(url.to_string())),
+			Some(("ws" | "wss", _)) => Ok(Endpoint::Ws(url.to_string())),
+			Some(("ipc" | "file", path)) => Ok(Endpoint::Ipc(PathBuf::from(path))),
+			None if url.ends_with(".ipc") => Ok(Endpoint::Ipc(PathBuf::from(url))),
+			_ => Err(ConfigError(format!("rpc url: {} has an unsupported scheme", url))),
+		}
+	}
+}
+
+/// A transport over http, websocket or ipc, the socket based ones reconnect on demand. With more
+/// than one endpoint configured it fails over between them.
+#[derive(Debug, Clone)]
+pub(crate) struct NodeTransport {
+	id: Arc<AtomicUsize>,
+	timeout: Duration,
+	inner: Inner,
+}
+
+#[derive(Debug, Clone)]
+enum Inner {
+	Http(Http),
+	Socket(Arc<SocketTransport>),
+	Pool(Arc<Pool>),
+}
+
+impl NodeTransport {
+	pub(crate) fn new(config: &NetworkConfig) -> crate::Result<Self> {
+		if config.upstreams.is_empty() {
+			return Self::single(&config.rpc_url, config);
+		}
+
+		let mut upstreams = Vec::with_capacity(config.upstreams.len() + 1);
+		for url in std::iter::once(&config.rpc_url).chain(&config.upstreams) {
+			upstreams.push((url.clone(), Self::single(url, config)?));
+		}
+		Ok(NodeTransport {
+			id: Arc::new(AtomicUsize::new(1)),
+			timeout: config.rpc_timeout(),
+			inner: Inner::Pool(Pool::new(upstreams, config)),
+		})
+	}
+
+	fn single(url: &str, config: &NetworkConfig) -> crate::Result<Self> {
+		let inner = match url.parse()? {
+			Endpoint::Http(url) => Inner::Http(Http::new(&url)?),
+			endpoint => Inner::Socket(Arc::new(SocketTransport {
+				endpoint,
+				current: RwLock::new(None),
+				connecting: tokio::sync::Mutex::new(0),
+				attempts: config.reconnect_attempts,
+				backoff: Duration::from_millis(config.reconnect_backoff_ms),
+			})),
+		};
+		Ok(NodeTransport {
+			id: Arc::new(AtomicUsize::new(1)),
+			timeout: config.rpc_timeout(),
+			inner,
+		})
+	}
+}
+
+impl Transport for NodeTransport {
+	type Out = BoxFuture<'static, web3::Result<Value>>;
+
+	fn prepare(&self, method: &str, params: Vec<Value>) -> (RequestId, Call) {
+		let id = self.id.fetch_add(1, Ordering::AcqRel);
+		(id, helpers::build_request(id, method, params))
+	}
+
+	fn send(&self, id: RequestId, call: Call) -> Self::Out {
+		let timeout = self.timeout;
+		let request = match &self.inner {
+			Inner::Http(http) => http.send(id, call),
+			Inner::Socket(socket) => socket.clone().send(id, call).boxed(),
+			// Each upstream applies the timeout on its own.
+			Inner::Pool(pool) => return pool.clone().send(id, call).boxed(),
+		};
+		async move {
+			tokio::time::timeout(timeout, request).await.unwrap_or_else(|_| {
+				Err(Web3Error::Transport(TransportError::Message(RPC_TIMEOUT_MSG.to_string())))
+			})
+		}
+		.boxed()
+	}
+}
+
+/// Subscriptions live on the current socket, they are not restored after a reconnect.
+impl DuplexTransport for NodeTransport {
+	type NotificationStream = BoxStream<'static, Value>;
+
+	fn subscribe(&self, id: SubscriptionId) -> web3::Result<Self::NotificationStream> {
+		match &self.inner {
+			Inner::Http(_) => Err(transport_error("subscriptions require a ws or ipc endpoint")),
+			Inner::Socket(socket) => match socket.connected()? {
+				Connection::Ws(ws) => Ok(ws.subscribe(id)?.boxed()),
+				Connection::Ipc(ipc) => Ok(ipc.subscribe(id)?.boxed()),
+			},
+			Inner::Pool(pool) => pool.primary().subscribe(id),
+		}
+	}
+
+	fn unsubscribe(&self, id: SubscriptionId) -> web3::Result<()> {
+		match &self.inner {
+			Inner::Http(_) => Err(transport_error("subscriptions require a ws or ipc endpoint")),
+			Inner::Socket(socket) => match socket.connected()? {
+				Connection::Ws(ws) => ws.unsubscribe(id),
+				Connection::Ipc(ipc) => ipc.unsubscribe(id),
+			},
+			Inner::Pool(pool) => pool.primary().unsubscribe(id),
+		}
+	}
+}
+
+/// Requests which may be sent twice without changing the outcome. A raw transaction the node
+/// accepted before failing would be answered as known or its nonce as too low the second time.
+pub(crate) fn is_idempotent(method: &str) -> bool {
+	!matches!(method, "eth_sendTransaction" | "eth_sendRawTransaction" | "personal_sendTransaction")
+}
+
+#[derive(Debug)]
+struct SocketTransport {
+	endpoint: Endpoint,
+	/// The live connection and its generation, cleared once it is found broken.
+	current: RwLock<Option<(u64, Connection)>>,
+	/// Serializes reconnects, holds the last generation handed out.
+	connecting: tokio::sync::Mutex<u64>,
+	attempts: u32,
+	backoff: Duration,
+}
+
+#[derive(Debug, Clone)]
+enum Connection {
+	Ws(WebSocket),
+	Ipc(Ipc),
+}
+
+impl SocketTransport {
+	async fn send(self: Arc<Self>, id: RequestId, call: Call) -> web3::Result<Value> {
+		let method = match &call {
+			Call::MethodCall(method_call) => method_call.method.clone(),
+			_ => String::new(),
+		};
+
+		let (generation, connection) = self.connection().await?;
+		match connection.send(id, call.clone()).await {
+			Err(Web3Error::Transport(err)) => {
+				warn!("Connection to {:?} dropped: {}", self.endpoint, err);
+				self.invalidate(generation);
+				if !is_idempotent(&method) {
+					return Err(Web3Error::Transport(err));
+				}
+				let (_, connection) = self.connection().await?;
+				connection.send(id, call).await
+			}
+			result => result,
+		}
+	}
+
+	fn connected(&self) -> web3::Result<Connection> {
+		match &*self.current.read().unwrap() {
+			Some((_, connection)) => Ok(connection.clone()),
+			None => Err(transport_error("not connected")),
+		}
+	}
+
+	async fn connection(&self) -> web3::Result<(u64, Connection)> {
+		if let Some(current) = self.current.read().unwrap().clone() {
+			return Ok(current);
+		}
+
+		let mut generation = self.connecting.lock().await;
+		if let Some(current) = self.current.read().unwrap().clone() {
+			return Ok(current);
+		}
+
+		let mut backoff = self.backoff;
+		let mut attempt = 1;
+		let connection = loop {
+			match self.connect().await {
+				Ok(connection) => break connection,
+				Err(err) if attempt >= self.attempts => return Err(err),
+				Err(err) => {
+					warn!("Connect to {:?} failed: {}, retry in {:?}", self.endpoint, err, backoff);
+					tokio::time::sleep(backoff).await;
+					backoff = (backoff * 2).min(MAX_BACKOFF);
+					attempt += 1;
+				}
+			}
+		};
+
+		info!("Connected to {:?}", self.endpoint);
+		*generation += 1;
+		*self.current.write().unwrap() = Some((*generation, connection.clone()));
+		Ok((*generation, connection))
+	}
+
+	async fn connect(&self) -> web3::Result<Connection> {
+		match &self.endpoint {
+			Endpoint::Ws(url) => Ok(Connection::Ws(WebSocket::new(url).await?)),
+			Endpoint::Ipc(path) => Ok(Connection::Ipc(Ipc::new(path).await?)),
+			Endpoint::Http(_) => unreachable!("http endpoints are not socket based"),
+		}
+	}
+
+	fn invalidate(&self, generation: u64) {
+		let mut current = self.current.write().unwrap();
+		if matches!(&*current, Some((gen, _)) if *gen == generation) {
+			*current = None;
+		}
+	}
+}
+
+impl Connection {
+	async fn send(&self, id: RequestId, call: Call) -> web3::Result<Value> {
+		match self {
+			Connection::Ws(ws) => ws.send(id, call).await,
+			Connection::Ipc(ipc) => ipc.send(id, call).await,
+		}
+	}
+}
+
+fn transport_error(msg: &str) -> Web3Error {
+	Web3Error::Transport(TransportError::Message(msg.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+	use std::path::PathBuf;
+
+	use super::Endpoint;
+
+	#[test]
+	fn test_parse_endpoint() {
+		let endpoint = "https://node:8545".parse::<Endpoint>().unwrap();
+		assert_eq!(endpoint, Endpoint::Http("https://node:8545".to_string()));
+		let endpoint = "ws://node:8546".parse::<Endpoint>().unwrap();
+		assert_eq!(endpoint, Endpoint::Ws("ws://node:8546".to_string()));
+		let endpoint = "ipc:///tmp/geth.ipc".parse::<Endpoint>().unwrap();
+		assert_eq!(endpoint, Endpoint::Ipc(PathBuf::from("/tmp/geth.ipc")));
+		let endpoint = "/tmp/geth.ipc".parse::<Endpoint>().unwrap();
+		assert_eq!(endpoint, Endpoint::Ipc(PathBuf::from("/tmp/geth.ipc")));
+		assert!("tcp://node:8545".parse::<Endpoint>().is_err());
+	}
+}
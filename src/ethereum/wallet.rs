@@ -0,0 +1,235 @@
+use std::{fs, sync::RwLock};
+
+use bip39::Mnemonic;
+use futures::future::join_all;
+use hmac::{Hmac, Mac};
+use log::info;
+use once_cell::sync::OnceCell;
+use secp256k1::{PublicKey, Secp256k1, SecretKey};
+use serde::{Deserialize, Serialize};
+use sha2::Sha512;
+use utoipa::ToSchema;
+use web3::{
+	signing::{Key, SecretKeyRef},
+	types::H160,
+};
+
+use super::{
+	account::{balance, Balance, BalanceQuery},
+	Network,
+};
+use crate::{
+	config::WalletConfig,
+	error::Error::{AnyError, ConfigError, Conflict, InvalidParam},
+	Result,
+};
+
+/// Mnemonic of the wallet, takes precedence over `mnemonic_file` of the config.
+const MNEMONIC_ENV: &str = "WEB3_MNEMONIC";
+/// BIP-44 path of the external chain of the first ethereum account, the index is appended.
+const ACCOUNT_PATH: [u32; 4] = [44 | HARDENED, 60 | HARDENED, HARDENED, 0];
+const HARDENED: u32 = 1 << 31;
+/// Balances asked from the node at once when listing the accounts.
+const BALANCE_CHUNK: usize = 20;
+/// Upper bound of the derived accounts.
+const MAX_ACCOUNTS: u32 = 1000;
+
+static WALLET: OnceCell<Option<HdWallet>> = OnceCell::new();
+
+/// Accounts derived from a BIP-39 mnemonic along `m/44'/60'/0'/0/i`.
+struct HdWallet {
+	/// Extended key of `m/44'/60'/0'/0`.
+	parent: ExtendedKey,
+	/// Derived accounts by index.
+	accounts: RwLock<Vec<(H160, SecretKey)>>,
+}
+
+#[derive(Clone)]
+struct ExtendedKey {
+	key: SecretKey,
+	chain_code: [u8; 32],
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub(crate) struct DeriveAccountsRequest {
+	/// Accounts the wallet should have, the missing ones are derived.
+	count: u32,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct WalletQuery {
+	/// Only the first accounts, all derived ones when left out.
+	count: Option<u32>,
+	precision: Option<usize>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub(crate) struct WalletAccount {
+	index: u32,
+	path: String,
+	#[schema(value_type = String)]
+	address: H160,
+	balance: Balance,
+}
+
+impl ExtendedKey {
+	fn master(seed: &[u8]) -> Result<ExtendedKey> {
+		let (key, chain_code) = hmac_sha512(b"Bitcoin seed", &[seed]);
+		let key = SecretKey::from_slice(&key).map_err(|e| AnyError(e.into()))?;
+		Ok(ExtendedKey { key, chain_code })
+	}
+
+	/// BIP-32 derivation of a private child key.
+	fn child(&self, index: u32) -> Result<ExtendedKey> {
+		let (tweak, chain_code) = match index & HARDENED {
+			0 => {
+				let public = PublicKey::from_secret_key(&Secp256k1::signing_only(), &self.key);
+				hmac_sha512(&self.chain_code, &[&public.serialize(), &index.to_be_bytes()])
+			}
+			_ => hmac_sha512(&self.chain_code, &[&[0], self.key.as_ref(), &index.to_be_bytes()]),
+		};
+		let mut key = self.key;
+		key.add_assign(&tweak).map_err(|e| AnyError(e.into()))?;
+		Ok(ExtendedKey { key, chain_code })
+	}
+}
+
+fn hmac_sha512(key: &[u8], data: &[&[u8]]) -> ([u8; 32], [u8; 32]) {
+	let mut mac = Hmac::<Sha512>::new_from_slice(key).expect("hmac takes keys of any size");
+	data.iter().for_each(|data| mac.update(data));
+	let output = mac.finalize().into_bytes();
+	let (mut left, mut right) = ([0; 32], [0; 32]);
+	left.copy_from_slice(&output[..32]);
+	right.copy_from_slice(&output[32..]);
+	(left, right)
+}
+
+impl HdWallet {
+	fn new(mnemonic: &str, accounts: u32) -> Result<HdWallet> {
+		let mnemonic = Mnemonic::parse(mnemonic)
+			.map_err(|e| ConfigError(format!("mnemonic is invalid, {}", e)))?;
+		let mut parent = ExtendedKey::master(&mnemonic.to_seed(""))?;
+		for index in ACCOUNT_PATH {
+			parent = parent.child(index)?;
+		}
+		let wallet = HdWallet { parent, accounts: RwLock::new(Vec::new()) };
+		wallet.derive(accounts)?;
+		Ok(wallet)
+	}
+
+	/// Derive the accounts up to `count`.
+	fn derive(&self, count: u32) -> Result<()> {
+		if count > MAX_ACCOUNTS {
+			return Err(InvalidParam(format!("count: {} exceeds {}", count, MAX_ACCOUNTS)));
+		}
+		let mut accounts = self.accounts.write().unwrap();
+		for index in accounts.len() as u32..count {
+			let key = self.parent.child(index)?.key;
+			accounts.push((SecretKeyRef::new(&key).address(), key));
+		}
+		Ok(())
+	}
+
+	fn addresses(&self, count: Option<u32>) -> Vec<H160> {
+		let accounts = self.accounts.read().unwrap();
+		let count = count.map_or(accounts.len(), |count| accounts.len().min(count as usize));
+		accounts[..count].iter().map(|(address, _)| *address).collect()
+	}
+
+	fn key(&self, address: H160) -> Option<SecretKey> {
+		let accounts = self.accounts.read().unwrap();
+		accounts.iter().find(|(account, _)| *account == address).map(|(_, key)| *key)
+	}
+}
+
+/// Derive the configured accounts of the mnemonic, without one the wallet stays disabled.
+pub(crate) fn init(config: &WalletConfig) -> Result<()> {
+	let mnemonic = match (std::env::var(MNEMONIC_ENV).ok(), &config.mnemonic_file) {
+		(Some(mnemonic), _) => Some(mnemonic),
+		(None, Some(path)) => Some(
+			fs::read_to_string(path)
+				.map_err(|e| ConfigError(format!("read {} failed, {}", path.display(), e)))?,
+		),
+		(None, None) => None,
+	};
+	let wallet = match mnemonic {
+		Some(mnemonic) => {
+			let wallet = HdWallet::new(mnemonic.trim(), config.accounts)?;
+			info!("Derived {} wallet accounts", config.accounts);
+			Some(wallet)
+		}
+		None => None,
+	};
+	WALLET.set(wallet).map_err(|_| ConfigError("wallet is initialized twice".to_string()))
+}
+
+fn wallet() -> Result<&'static HdWallet> {
+	WALLET
+		.get()
+		.expect("wallet is not initialized")
+		.as_ref()
+		.ok_or_else(|| Conflict(format!("wallet is disabled, {} is required", MNEMONIC_ENV)))
+}
+
+/// The key of a derived account.
+pub(crate) fn key(address: H160) -> Option<SecretKey> {
+	WALLET.get().and_then(Option::as_ref).and_then(|wallet| wallet.key(address))
+}
+
+/// Derive the accounts up to `count` and list them all.
+pub(crate) async fn derive_accounts(
+	network: &Network,
+	request: DeriveAccountsRequest,
+) -> Result<Vec<WalletAccount>> {
+	wallet()?.derive(request.count)?;
+	list_accounts(network, WalletQuery::default()).await
+}
+
+/// The derived accounts with their balances on the network.
+pub(crate) async fn list_accounts(
+	network: &Network,
+	query: WalletQuery,
+) -> Result<Vec<WalletAccount>> {
+	let addresses = wallet()?.addresses(query.count);
+	let query = BalanceQuery { precision: query.precision };
+	let mut accounts = Vec::with_capacity(addresses.len());
+	for chunk in addresses.chunks(BALANCE_CHUNK) {
+		let balances = join_all(chunk.iter().map(|address| balance(network, *address, &query)));
+		for (address, balance) in chunk.iter().zip(balances.await) {
+			let index = accounts.len() as u32;
+			accounts.push(WalletAccount {
+				index,
+				path: format!("m/44'/60'/0'/0/{}", index),
+				address: *address,
+				balance: balance?,
+			});
+		}
+	}
+	Ok(accounts)
+}
+
+#[cfg(test)]
+mod tests {
+	use web3::types::H160;
+
+	use super::HdWallet;
+
+	#[test]
+	fn test_derive_accounts() {
+		let mnemonic = "test test test test test test test test test test test junk";
+		let wallet = HdWallet::new(mnemonic, 2).unwrap();
+		let addresses = [
+			"0xf39fd6e51aad88f6f4ce6ab8827279cfffb92266",
+			"0x70997970c51812dc3a010c7d01b50e0d17dc79c8",
+		];
+		let addresses = addresses.map(|address| address.parse::<H160>().unwrap());
+		assert_eq!(wallet.addresses(None), addresses);
+		assert_eq!(wallet.addresses(Some(1)), addresses[..1]);
+		assert!(wallet.key(addresses[1]).is_some());
+
+		wallet.derive(3).unwrap();
+		assert_eq!(wallet.addresses(None).len(), 3);
+		assert!(wallet.derive(1001).is_err());
+		assert!(HdWallet::new("test test test", 1).is_err());
+	}
+}
@@ -1,19 +1,34 @@
-#![feature(absolute_path)]
-
-use std::time::Duration;
+#![allow(clippy::upper_case_acronyms, clippy::enum_variant_names)]
 
 use axum::http::StatusCode;
 
-use axum::{error_handling::HandleErrorLayer, routing::get, Router};
+use axum::{error_handling::HandleErrorLayer, routing::get, Json, Router};
 use chrono::Local;
 use log::info;
-use routes::eth_routes;
+use routes::{contract_routes, eth_routes, signer_routes, ResultInfo};
 
 use tower::{BoxError, ServiceBuilder};
 
-use self::ethereum::{
-	contract::{DeployContractRequest, InvokeContractRequest},
-	transaction::TxRequest,
+use self::{
+	contracts::{
+		compiler::{CompileRequest, CompileResult},
+		registry::{ContractDetail, ContractSummary, UploadContractRequest},
+	},
+	ethereum::{
+		account::Balance,
+		contract::{DeployContractRequest, InvokeContractRequest},
+		deployment::{Deployment, DeploymentStatus},
+		message::{MessageSignature, SignMessageRequest, TypedData, Verification, VerifyRequest},
+		signer::{ImportSignerRequest, SignerInfo},
+		transaction::{
+			nonce::{FillNoncesRequest, FilledNonce, NonceStatus},
+			offline::{BroadcastRequest, DecodedTx, SignedTx},
+			replace::ReplaceTxRequest,
+			tracker::{TxInfo, TxStatus},
+			TxOptions, TxRequest,
+		},
+		wallet::{DeriveAccountsRequest, WalletAccount},
+	},
 };
 use tracing_subscriber::{
 	fmt, fmt::time::FormatTime, prelude::__tracing_subscriber_SubscriberExt,
@@ -22,6 +37,8 @@ use tracing_subscriber::{
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
+mod cli;
+mod config;
 mod contracts;
 mod error;
 mod ethereum;
@@ -32,35 +49,49 @@ pub type Result<T, E = crate::error::Error> = core::result::Result<T, E>;
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
 	tracing_subscriber::registry()
-		.with(fmt::layer().with_timer(LogTimer).with_filter(
+		.with(fmt::layer().with_writer(std::io::stderr).with_timer(LogTimer).with_filter(
 			EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
 		))
 		.init();
 
 	info!("Starting up...");
 
+	let config = config::init()?;
+	contracts::registry::init(&config.contracts)?;
+	ethereum::deployment::init(&config.contracts.deployments_db)?;
+	ethereum::signer::init(&config.signers).await?;
+	ethereum::wallet::init(&config.wallet)?;
+
+	let args = std::env::args().skip(1).collect::<Vec<_>>();
+	if !args.is_empty() {
+		return Ok(cli::run(&args).await?);
+	}
+	ethereum::init().await?;
+
 	let app = Router::new()
 		.merge(SwaggerUi::new("/swagger-ui/*tail").url("/api-doc/openapi.json", ApiDoc::openapi()))
 		.route("/", get(|| async { "Hello, World!" }))
+		.nest("/contracts", contract_routes())
+		.nest("/signers", signer_routes())
 		.nest("/eth", eth_routes())
+		.nest("/eth/:network", eth_routes())
 		.layer(
 			ServiceBuilder::new()
 				.layer(HandleErrorLayer::new(|error: BoxError| async move {
-					if error.is::<tower::timeout::error::Elapsed>() {
-						Ok(StatusCode::REQUEST_TIMEOUT)
+					let (status, code, msg) = if error.is::<tower::timeout::error::Elapsed>() {
+						(StatusCode::REQUEST_TIMEOUT, "REQUEST_TIMEOUT", error.to_string())
 					} else {
-						Err((
-							StatusCode::INTERNAL_SERVER_ERROR,
-							format!("Unhandled internal error: {}", error),
-						))
-					}
+						let msg = format!("Unhandled internal error: {}", error);
+						(StatusCode::INTERNAL_SERVER_ERROR, "INTERNAL_ERROR", msg)
+					};
+					(status, Json(ResultInfo::<()>::error(status, code, msg)))
 				}))
-				.timeout(Duration::from_secs(60))
+				.timeout(config.server.request_timeout())
 				.into_inner(),
 		);
 
-	let addr = if cfg!(debug_assertions) { "127.0.0.1:8080" } else { "0.0.0.0:8080" };
-	axum::Server::bind(&addr.parse().unwrap()).serve(app.into_make_service()).await?;
+	info!("Listening on {}", config.server.bind_addr);
+	axum::Server::bind(&config.server.bind_addr).serve(app.into_make_service()).await?;
 
 	Ok(())
 }
@@ -83,7 +114,59 @@ impl FormatTime for LogTimer {
 		self::routes::eth_api::deploy_contract,
 		self::routes::eth_api::call_contract,
 		self::routes::eth_api::query_contract,
+		self::routes::eth_api::list_deployments,
+		self::routes::eth_api::get_deployment,
+		self::routes::eth_api::get_nonces,
+		self::routes::eth_api::fill_account_nonces,
+		self::routes::eth_api::get_transaction,
+		self::routes::eth_api::sign_transaction,
+		self::routes::eth_api::broadcast_transaction,
+		self::routes::eth_api::speed_up_transaction,
+		self::routes::eth_api::cancel_transaction,
+		self::routes::eth_api::wallet_accounts,
+		self::routes::eth_api::derive_wallet_accounts,
+		self::routes::eth_api::sign_message,
+		self::routes::eth_api::verify_message,
+		self::routes::contract_api::list_contracts,
+		self::routes::contract_api::get_contract,
+		self::routes::contract_api::upload_contract,
+		self::routes::contract_api::delete_contract,
+		self::routes::contract_api::compile_contract,
+		self::routes::signer_api::list_signers,
+		self::routes::signer_api::import_signer,
+		self::routes::signer_api::delete_signer,
 	),
-	components(schemas(TxRequest, DeployContractRequest, InvokeContractRequest))
+	components(schemas(
+		TxRequest,
+		TxOptions,
+		Balance,
+		DeployContractRequest,
+		InvokeContractRequest,
+		UploadContractRequest,
+		ContractSummary,
+		ContractDetail,
+		CompileRequest,
+		CompileResult,
+		Deployment,
+		DeploymentStatus,
+		NonceStatus,
+		FillNoncesRequest,
+		FilledNonce,
+		TxInfo,
+		TxStatus,
+		ReplaceTxRequest,
+		SignerInfo,
+		ImportSignerRequest,
+		WalletAccount,
+		DeriveAccountsRequest,
+		SignedTx,
+		BroadcastRequest,
+		DecodedTx,
+		SignMessageRequest,
+		VerifyRequest,
+		MessageSignature,
+		Verification,
+		TypedData
+	))
 )]
 struct ApiDoc;
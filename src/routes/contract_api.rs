@@ -0,0 +1,87 @@
+use crate::contracts::{
+	compiler::{self, CompileRequest, CompileResult},
+	registry::{self, ContractDetail, ContractSummary, UploadContractRequest},
+};
+
+use super::{
+	extract::{Json, Path},
+	ApiResult, ResultInfo,
+};
+
+#[utoipa::path(
+	get,
+	path = "/contracts",
+	responses(
+		(status = 200, description = "List registered contracts successfully"),
+	),
+)]
+pub(crate) async fn list_contracts() -> ApiResult<Vec<ContractSummary>> {
+	Ok(Json(ResultInfo::ok(registry::list())))
+}
+
+#[utoipa::path(
+	get,
+	path = "/contracts/{name}",
+	responses(
+		(status = 200, description = "Get contract artifact successfully"),
+		(status = 400, description = "Contract name is invalid"),
+		(status = 404, description = "Contract not found"),
+	),
+	params(
+		("name" = String, Path, description = "contract name"),
+	),
+)]
+pub(crate) async fn get_contract(Path(name): Path<String>) -> ApiResult<ContractDetail> {
+	Ok(Json(ResultInfo::ok(registry::detail(&name)?)))
+}
+
+#[utoipa::path(
+	post,
+	path = "/contracts",
+	request_body = UploadContractRequest,
+	responses(
+		(status = 200, description = "Upload contract artifact successfully"),
+		(status = 400, description = "Name, abi or bytecode is invalid"),
+		(status = 409, description = "Contract is shipped in the artifacts directory or compiled"),
+	),
+)]
+pub(crate) async fn upload_contract(
+	Json(payload): Json<UploadContractRequest>,
+) -> ApiResult<ContractDetail> {
+	Ok(Json(ResultInfo::ok(registry::upload(payload).await?)))
+}
+
+#[utoipa::path(
+	delete,
+	path = "/contracts/{name}",
+	responses(
+		(status = 200, description = "Delete contract artifact successfully"),
+		(status = 404, description = "Contract not found"),
+		(status = 409, description = "Contract is shipped in the artifacts directory or compiled"),
+	),
+	params(
+		("name" = String, Path, description = "contract name"),
+	),
+)]
+pub(crate) async fn delete_contract(Path(name): Path<String>) -> ApiResult<()> {
+	registry::delete(name).await?;
+	Ok(Json(ResultInfo::ok(())))
+}
+
+#[utoipa::path(
+	post,
+	path = "/contracts/compile",
+	request_body = CompileRequest,
+	responses(
+		(status = 200, description = "Compile source and register its contracts successfully"),
+		(status = 400, description = "Source name is invalid"),
+		(status = 404, description = "Source not found"),
+		(status = 422, description = "Source doesn't compile"),
+		(status = 500, description = "Solc can't be run"),
+	),
+)]
+pub(crate) async fn compile_contract(
+	Json(payload): Json<CompileRequest>,
+) -> ApiResult<CompileResult> {
+	Ok(Json(ResultInfo::ok(compiler::compile(&payload.source).await?)))
+}
@@ -1,39 +1,61 @@
-use axum::{extract::Path, http::StatusCode, Json};
-
-use log::error;
-use serde::Serialize;
-use serde_json::{json, Value};
+use serde::Deserialize;
+use serde_json::Value as JsonValue;
 use web3::types::{H160, H256};
 
-use crate::ethereum::{
-	account::{account_balance, accounts},
-	contract::{
-		call_sol_contract, deploy_sol_contract, query_sol_contract, DeployContractRequest,
-		InvokeContractRequest,
+use crate::{
+	error::Error::InvalidParam,
+	ethereum::{
+		account::{account_balance, accounts, Balance, BalanceQuery},
+		contract::{
+			call_sol_contract, deploy_sol_contract, query_sol_contract, DeployContractRequest,
+			InvokeContractRequest,
+		},
+		deployment::{self, Deployment, DeploymentQuery},
+		message::{self, MessageSignature, SignMessageRequest, Verification, VerifyRequest},
+		transaction::{
+			nonce::{fill_nonces, FillNoncesRequest, FilledNonce, NonceStatus},
+			offline::{broadcast, sign_offline, BroadcastRequest, DecodedTx, SignedTx},
+			replace::{replace_transaction, ReplaceTxRequest},
+			send_raw_transaction, send_transaction,
+			tracker::TxInfo,
+			TxRequest,
+		},
+		wallet::{self, DeriveAccountsRequest, WalletAccount, WalletQuery},
 	},
-	transaction::{send_raw_transaction, send_transaction, TxRequest},
 };
 
-use super::ResultInfo;
+use super::{
+	extract::{Json, Path, Query},
+	ApiResult, EthNetwork, ResultInfo,
+};
+
+#[derive(Deserialize)]
+pub(crate) struct AccountPath {
+	id: String,
+}
+
+impl AccountPath {
+	fn account(&self) -> crate::Result<H160> {
+		self.id.parse().map_err(|_| InvalidParam(format!("account: {} parse failed", self.id)))
+	}
+}
 
 #[utoipa::path(
 	get,
 	path = "/eth/accounts",
 	responses(
 		(status = 200, description = "List all accounts successfully"),
-		(status = 500, description = "List all accounts failed"),
-	)
+		(status = 404, description = "Network not found"),
+		(status = 502, description = "Node is unavailable"),
+		(status = 504, description = "Node timed out"),
+	),
+	params(
+		("x-eth-network" = Option<String>, Header, description = "target network name"),
+	),
 )]
-pub(crate) async fn eth_accounts() -> Json<Value> {
-	let result = match accounts().await {
-		Ok(accounts) => (StatusCode::OK, accounts),
-		Err(err) => {
-			error!(target: "ethereum", "Get eth accounts error: {}", err);
-			(StatusCode::INTERNAL_SERVER_ERROR, vec![])
-		}
-	};
-
-	build_json_value(result)
+pub(crate) async fn eth_accounts(EthNetwork(network): EthNetwork) -> ApiResult<Vec<H160>> {
+	let accounts = accounts(network).await?;
+	Ok(Json(ResultInfo::ok(accounts)))
 }
 
 #[utoipa::path(
@@ -41,22 +63,24 @@ pub(crate) async fn eth_accounts() -> Json<Value> {
 	path = "/eth/balance/{id}",
 	responses(
 		(status = 200, description = "Get account balance successfully"),
-		(status = 500, description = "Get account balance failed"),
+		(status = 400, description = "Account id or precision is invalid"),
+		(status = 404, description = "Network not found"),
+		(status = 502, description = "Node is unavailable"),
+		(status = 504, description = "Node timed out"),
 	),
 	params(
-		("id" = String, Path, description = "account id")
+		("id" = String, Path, description = "account id"),
+		("precision" = Option<usize>, Query, description = "fractional digits of the ether balance, 6 by default"),
+		("x-eth-network" = Option<String>, Header, description = "target network name"),
 	),
 )]
-pub(crate) async fn eth_balance(Path(id): Path<String>) -> Json<Value> {
-	let result = match account_balance(&id).await {
-		Ok(balance) => (StatusCode::OK, balance),
-		Err(err) => {
-			error!(target: "ethereum", "Get account balance error: {}", err);
-			(StatusCode::INTERNAL_SERVER_ERROR, "0".to_string())
-		}
-	};
-
-	build_json_value(result)
+pub(crate) async fn eth_balance(
+	EthNetwork(network): EthNetwork,
+	Path(path): Path<AccountPath>,
+	Query(query): Query<BalanceQuery>,
+) -> ApiResult<Balance> {
+	let balance = account_balance(network, &path.id, query).await?;
+	Ok(Json(ResultInfo::ok(balance)))
 }
 
 #[utoipa::path(
@@ -65,19 +89,21 @@ pub(crate) async fn eth_balance(Path(id): Path<String>) -> Json<Value> {
 	request_body = TxRequest,
 	responses(
 		(status = 200, description = "Send transaction successfully"),
-		(status = 500, description = "Send transaction failed")
-	)
+		(status = 400, description = "Transaction request is invalid"),
+		(status = 404, description = "Network not found"),
+		(status = 502, description = "Node rejected the transaction or is unavailable"),
+		(status = 504, description = "Node timed out"),
+	),
+	params(
+		("x-eth-network" = Option<String>, Header, description = "target network name"),
+	),
 )]
-pub(crate) async fn eth_transaction(Json(payload): Json<TxRequest>) -> Json<Value> {
-	let result = match send_transaction(payload).await {
-		Ok(addr) => (StatusCode::OK, addr),
-		Err(err) => {
-			error!(target: "ethereum", "Send transaction error: {}", err);
-			(StatusCode::INTERNAL_SERVER_ERROR, H256::zero())
-		}
-	};
-
-	build_json_value(result)
+pub(crate) async fn eth_transaction(
+	EthNetwork(network): EthNetwork,
+	Json(payload): Json<TxRequest>,
+) -> ApiResult<H256> {
+	let hash = send_transaction(network, payload).await?;
+	Ok(Json(ResultInfo::ok(hash)))
 }
 
 #[utoipa::path(
@@ -86,19 +112,21 @@ pub(crate) async fn eth_transaction(Json(payload): Json<TxRequest>) -> Json<Valu
 	request_body = TxRequest,
 	responses(
 		(status = 200, description = "Send raw transaction successfully"),
-		(status = 500, description = "Send raw transaction failed")
-	)
+		(status = 400, description = "Transaction request is invalid"),
+		(status = 404, description = "Network not found"),
+		(status = 502, description = "Node rejected the transaction or is unavailable"),
+		(status = 504, description = "Node timed out"),
+	),
+	params(
+		("x-eth-network" = Option<String>, Header, description = "target network name"),
+	),
 )]
-pub(crate) async fn eth_raw_transaction(Json(payload): Json<TxRequest>) -> Json<Value> {
-	let result = match send_raw_transaction(payload).await {
-		Ok(addr) => (StatusCode::OK, addr),
-		Err(err) => {
-			error!(target: "ethereum", "Send raw transaction error: {}", err);
-			(StatusCode::INTERNAL_SERVER_ERROR, H256::zero())
-		}
-	};
-
-	build_json_value(result)
+pub(crate) async fn eth_raw_transaction(
+	EthNetwork(network): EthNetwork,
+	Json(payload): Json<TxRequest>,
+) -> ApiResult<H256> {
+	let hash = send_raw_transaction(network, payload).await?;
+	Ok(Json(ResultInfo::ok(hash)))
 }
 
 #[utoipa::path(
@@ -106,20 +134,24 @@ pub(crate) async fn eth_raw_transaction(Json(payload): Json<TxRequest>) -> Json<
 	path = "/eth/contract/deploy",
 	request_body = DeployContractRequest,
 	responses(
-		(status = 200, description = "Deploy contract successfully"),
-		(status = 500, description = "Deploy contract failed")
-	)
+		(status = 200, description = "Submit contract deployment successfully"),
+		(status = 400, description = "Deploy request is invalid"),
+		(status = 404, description = "Network or contract not found"),
+		(status = 409, description = "Alias is taken"),
+		(status = 422, description = "Contract deployment reverted"),
+		(status = 502, description = "Node is unavailable"),
+		(status = 504, description = "Node timed out"),
+	),
+	params(
+		("x-eth-network" = Option<String>, Header, description = "target network name"),
+	),
 )]
-pub(crate) async fn deploy_contract(Json(payload): Json<DeployContractRequest>) -> Json<Value> {
-	let result = match deploy_sol_contract(payload).await {
-		Ok(addr) => (StatusCode::OK, addr),
-		Err(err) => {
-			error!(target: "ethereum", "Deploy contract error: {}", err);
-			(StatusCode::INTERNAL_SERVER_ERROR, H160::zero())
-		}
-	};
-
-	build_json_value(result)
+pub(crate) async fn deploy_contract(
+	EthNetwork(network): EthNetwork,
+	Json(payload): Json<DeployContractRequest>,
+) -> ApiResult<TxInfo> {
+	let tx = deploy_sol_contract(network, payload).await?;
+	Ok(Json(ResultInfo::ok(tx)))
 }
 
 #[utoipa::path(
@@ -127,47 +159,327 @@ pub(crate) async fn deploy_contract(Json(payload): Json<DeployContractRequest>)
 	path = "/eth/contract/call_fn",
 	request_body = InvokeContractRequest,
 	responses(
-		(status = 200, description = "Call contract function successfully"),
-		(status = 500, description = "Call contract function failed")
-	)
+		(status = 200, description = "Submit contract function call successfully"),
+		(status = 400, description = "Function or parameters are invalid"),
+		(status = 404, description = "Network, contract or deployment not found"),
+		(status = 422, description = "Contract function reverted"),
+		(status = 502, description = "Node is unavailable"),
+		(status = 504, description = "Node timed out"),
+	),
+	params(
+		("x-eth-network" = Option<String>, Header, description = "target network name"),
+	),
 )]
-pub(crate) async fn call_contract(Json(payload): Json<InvokeContractRequest>) -> Json<Value> {
-	let result = match call_sol_contract(payload).await {
-		Ok(addr) => (StatusCode::OK, addr),
-		Err(err) => {
-			error!(target: "ethereum", "call function of contract error: {}", err);
-			(StatusCode::INTERNAL_SERVER_ERROR, H256::zero())
-		}
-	};
-
-	build_json_value(result)
+pub(crate) async fn call_contract(
+	EthNetwork(network): EthNetwork,
+	Json(payload): Json<InvokeContractRequest>,
+) -> ApiResult<H256> {
+	let hash = call_sol_contract(network, payload).await?;
+	Ok(Json(ResultInfo::ok(hash)))
 }
 
 #[utoipa::path(
 	post,
 	path = "/eth/contract/query_fn",
-	request_body = InvokeContractRequest<()>,
+	request_body = InvokeContractRequest,
 	responses(
 		(status = 200, description = "Query contract function successfully"),
-		(status = 500, description = "Query contract function failed")
-	)
-)]
-pub(crate) async fn query_contract(Json(payload): Json<InvokeContractRequest>) -> Json<Value> {
-	let result = match query_sol_contract(payload).await {
-		Ok(addr) => (StatusCode::OK, addr),
-		Err(err) => {
-			error!(target: "ethereum", "query function of contract error: {}", err);
-			(StatusCode::INTERNAL_SERVER_ERROR, vec![])
-		}
-	};
-
-	build_json_value(result)
-}
-
-fn build_json_value<T: Serialize>(result_tuple: (StatusCode, T)) -> Json<Value> {
-	Json(json!(ResultInfo::new(
-		result_tuple.0.as_u16(),
-		result_tuple.0.canonical_reason().unwrap().to_string(),
-		result_tuple.1
-	)))
+		(status = 400, description = "Function or parameters are invalid"),
+		(status = 404, description = "Network, contract or deployment not found"),
+		(status = 422, description = "Contract function reverted"),
+		(status = 502, description = "Node is unavailable"),
+		(status = 504, description = "Node timed out"),
+	),
+	params(
+		("x-eth-network" = Option<String>, Header, description = "target network name"),
+	),
+)]
+pub(crate) async fn query_contract(
+	EthNetwork(network): EthNetwork,
+	Json(payload): Json<InvokeContractRequest>,
+) -> ApiResult<JsonValue> {
+	let results = query_sol_contract(network, payload).await?;
+	Ok(Json(ResultInfo::ok(results)))
+}
+
+#[utoipa::path(
+	get,
+	path = "/eth/deployments",
+	responses(
+		(status = 200, description = "List deployments successfully"),
+		(status = 404, description = "Network not found"),
+	),
+	params(
+		("contract_name" = Option<String>, Query, description = "only deployments of this contract"),
+		("x-eth-network" = Option<String>, Header, description = "target network name"),
+	),
+)]
+pub(crate) async fn list_deployments(
+	EthNetwork(network): EthNetwork,
+	Query(query): Query<DeploymentQuery>,
+) -> ApiResult<Vec<Deployment>> {
+	Ok(Json(ResultInfo::ok(deployment::list(network, &query)?)))
+}
+
+#[utoipa::path(
+	get,
+	path = "/eth/deployments/{id}",
+	responses(
+		(status = 200, description = "Get deployment successfully"),
+		(status = 404, description = "Network or deployment not found"),
+	),
+	params(
+		("id" = String, Path, description = "address or alias of the deployment"),
+		("x-eth-network" = Option<String>, Header, description = "target network name"),
+	),
+)]
+pub(crate) async fn get_deployment(
+	EthNetwork(network): EthNetwork,
+	Path(path): Path<AccountPath>,
+) -> ApiResult<Deployment> {
+	Ok(Json(ResultInfo::ok(deployment::lookup(network, &path.id)?)))
+}
+
+#[utoipa::path(
+	get,
+	path = "/eth/nonces/{id}",
+	responses(
+		(status = 200, description = "Get nonces of the account successfully"),
+		(status = 400, description = "Account id is invalid"),
+		(status = 404, description = "Network not found"),
+		(status = 502, description = "Node is unavailable"),
+		(status = 504, description = "Node timed out"),
+	),
+	params(
+		("id" = String, Path, description = "account id"),
+		("x-eth-network" = Option<String>, Header, description = "target network name"),
+	),
+)]
+pub(crate) async fn get_nonces(
+	EthNetwork(network): EthNetwork,
+	Path(path): Path<AccountPath>,
+) -> ApiResult<NonceStatus> {
+	let account = path.account()?;
+	Ok(Json(ResultInfo::ok(network.nonces.status(network, account).await?)))
+}
+
+#[utoipa::path(
+	post,
+	path = "/eth/nonces/{id}/fill",
+	request_body = FillNoncesRequest,
+	responses(
+		(status = 200, description = "Fill gaps and cancel stuck nonces successfully"),
+		(status = 400, description = "Account, key or nonces are invalid"),
+		(status = 404, description = "Network not found"),
+		(status = 502, description = "Node rejected a transaction or is unavailable"),
+		(status = 504, description = "Node timed out"),
+	),
+	params(
+		("id" = String, Path, description = "account id"),
+		("x-eth-network" = Option<String>, Header, description = "target network name"),
+	),
+)]
+pub(crate) async fn fill_account_nonces(
+	EthNetwork(network): EthNetwork,
+	Path(path): Path<AccountPath>,
+	Json(payload): Json<FillNoncesRequest>,
+) -> ApiResult<Vec<FilledNonce>> {
+	let account = path.account()?;
+	Ok(Json(ResultInfo::ok(fill_nonces(network, account, payload).await?)))
+}
+
+#[derive(Deserialize)]
+pub(crate) struct TxPath {
+	hash: H256,
+}
+
+#[utoipa::path(
+	get,
+	path = "/eth/tx/{hash}",
+	responses(
+		(status = 200, description = "Get transaction status successfully"),
+		(status = 400, description = "Transaction hash is invalid"),
+		(status = 404, description = "Network or transaction not found"),
+		(status = 502, description = "Node is unavailable"),
+		(status = 504, description = "Node timed out"),
+	),
+	params(
+		("hash" = String, Path, description = "transaction hash"),
+		("x-eth-network" = Option<String>, Header, description = "target network name"),
+	),
+)]
+pub(crate) async fn get_transaction(
+	EthNetwork(network): EthNetwork,
+	Path(path): Path<TxPath>,
+) -> ApiResult<TxInfo> {
+	Ok(Json(ResultInfo::ok(network.tracker.status(network, path.hash).await?)))
+}
+
+#[utoipa::path(
+	post,
+	path = "/eth/tx/sign",
+	request_body = TxRequest,
+	responses(
+		(status = 200, description = "Sign transaction without sending it successfully"),
+		(status = 400, description = "Transaction request is invalid or lacks nonce, gas or fees"),
+		(status = 404, description = "Network or signer not found"),
+	),
+	params(
+		("x-eth-network" = Option<String>, Header, description = "target network name"),
+	),
+)]
+pub(crate) async fn sign_transaction(
+	EthNetwork(network): EthNetwork,
+	Json(payload): Json<TxRequest>,
+) -> ApiResult<SignedTx> {
+	Ok(Json(ResultInfo::ok(sign_offline(network, payload).await?)))
+}
+
+#[utoipa::path(
+	post,
+	path = "/eth/tx/broadcast",
+	request_body = BroadcastRequest,
+	responses(
+		(status = 200, description = "Decode and send signed transaction successfully"),
+		(status = 400, description = "Transaction doesn't decode, belongs to another chain or its nonce is used"),
+		(status = 404, description = "Network not found"),
+		(status = 502, description = "Node rejected the transaction or is unavailable"),
+		(status = 504, description = "Node timed out"),
+	),
+	params(
+		("x-eth-network" = Option<String>, Header, description = "target network name"),
+	),
+)]
+pub(crate) async fn broadcast_transaction(
+	EthNetwork(network): EthNetwork,
+	Json(payload): Json<BroadcastRequest>,
+) -> ApiResult<DecodedTx> {
+	Ok(Json(ResultInfo::ok(broadcast(network, payload).await?)))
+}
+
+#[utoipa::path(
+	post,
+	path = "/eth/tx/{hash}/speedup",
+	request_body = ReplaceTxRequest,
+	responses(
+		(status = 200, description = "Send the transaction again with higher fees successfully"),
+		(status = 400, description = "Key or fees are invalid"),
+		(status = 404, description = "Network or transaction not found"),
+		(status = 409, description = "Transaction is already mined"),
+		(status = 502, description = "Node rejected the replacement or is unavailable"),
+		(status = 504, description = "Node timed out"),
+	),
+	params(
+		("hash" = String, Path, description = "transaction hash"),
+		("x-eth-network" = Option<String>, Header, description = "target network name"),
+	),
+)]
+pub(crate) async fn speed_up_transaction(
+	EthNetwork(network): EthNetwork,
+	Path(path): Path<TxPath>,
+	Json(payload): Json<ReplaceTxRequest>,
+) -> ApiResult<TxInfo> {
+	Ok(Json(ResultInfo::ok(replace_transaction(network, path.hash, payload, false).await?)))
+}
+
+#[utoipa::path(
+	post,
+	path = "/eth/tx/{hash}/cancel",
+	request_body = ReplaceTxRequest,
+	responses(
+		(status = 200, description = "Replace the transaction by an empty transfer successfully"),
+		(status = 400, description = "Key or fees are invalid"),
+		(status = 404, description = "Network or transaction not found"),
+		(status = 409, description = "Transaction is already mined"),
+		(status = 502, description = "Node rejected the replacement or is unavailable"),
+		(status = 504, description = "Node timed out"),
+	),
+	params(
+		("hash" = String, Path, description = "transaction hash"),
+		("x-eth-network" = Option<String>, Header, description = "target network name"),
+	),
+)]
+pub(crate) async fn cancel_transaction(
+	EthNetwork(network): EthNetwork,
+	Path(path): Path<TxPath>,
+	Json(payload): Json<ReplaceTxRequest>,
+) -> ApiResult<TxInfo> {
+	Ok(Json(ResultInfo::ok(replace_transaction(network, path.hash, payload, true).await?)))
+}
+
+#[utoipa::path(
+	get,
+	path = "/eth/wallet/accounts",
+	responses(
+		(status = 200, description = "List derived wallet accounts with balances successfully"),
+		(status = 400, description = "Precision is invalid"),
+		(status = 404, description = "Network not found"),
+		(status = 409, description = "Wallet is disabled, no mnemonic is configured"),
+		(status = 502, description = "Node is unavailable"),
+		(status = 504, description = "Node timed out"),
+	),
+	params(
+		("count" = Option<u32>, Query, description = "only the first accounts, all derived ones by default"),
+		("precision" = Option<usize>, Query, description = "fractional digits of the ether balances, 6 by default"),
+		("x-eth-network" = Option<String>, Header, description = "target network name"),
+	),
+)]
+pub(crate) async fn wallet_accounts(
+	EthNetwork(network): EthNetwork,
+	Query(query): Query<WalletQuery>,
+) -> ApiResult<Vec<WalletAccount>> {
+	Ok(Json(ResultInfo::ok(wallet::list_accounts(network, query).await?)))
+}
+
+#[utoipa::path(
+	post,
+	path = "/eth/wallet/accounts",
+	request_body = DeriveAccountsRequest,
+	responses(
+		(status = 200, description = "Derive wallet accounts successfully"),
+		(status = 400, description = "Count exceeds the limit"),
+		(status = 404, description = "Network not found"),
+		(status = 409, description = "Wallet is disabled, no mnemonic is configured"),
+		(status = 502, description = "Node is unavailable"),
+		(status = 504, description = "Node timed out"),
+	),
+	params(
+		("x-eth-network" = Option<String>, Header, description = "target network name"),
+	),
+)]
+pub(crate) async fn derive_wallet_accounts(
+	EthNetwork(network): EthNetwork,
+	Json(payload): Json<DeriveAccountsRequest>,
+) -> ApiResult<Vec<WalletAccount>> {
+	Ok(Json(ResultInfo::ok(wallet::derive_accounts(network, payload).await?)))
+}
+
+#[utoipa::path(
+	post,
+	path = "/eth/message/sign",
+	request_body = SignMessageRequest,
+	responses(
+		(status = 200, description = "Sign message or typed data successfully"),
+		(status = 400, description = "Message, typed data or key is invalid"),
+		(status = 404, description = "Signer or struct type not found"),
+	),
+)]
+pub(crate) async fn sign_message(
+	Json(payload): Json<SignMessageRequest>,
+) -> ApiResult<MessageSignature> {
+	Ok(Json(ResultInfo::ok(message::sign(payload)?)))
+}
+
+#[utoipa::path(
+	post,
+	path = "/eth/message/verify",
+	request_body = VerifyRequest,
+	responses(
+		(status = 200, description = "Recover the signer of message or typed data successfully"),
+		(status = 400, description = "Message, typed data or signature is invalid"),
+		(status = 404, description = "Struct type not found"),
+	),
+)]
+pub(crate) async fn verify_message(Json(payload): Json<VerifyRequest>) -> ApiResult<Verification> {
+	Ok(Json(ResultInfo::ok(message::verify(payload)?)))
 }
@@ -0,0 +1,109 @@
+use std::error::Error as StdError;
+
+use axum::{
+	async_trait,
+	extract::{FromRequest, RequestParts},
+	response::{IntoResponse, Response},
+};
+use serde::Serialize;
+
+use crate::error::Error::{self, InvalidParam};
+
+/// Wraps an axum extractor so that its rejections are answered like every other error.
+macro_rules! extractor {
+	($(#[$doc:meta])* $name:ident) => {
+		$(#[$doc])*
+		pub(crate) struct $name<T>(pub T);
+
+		#[async_trait]
+		impl<B, T> FromRequest<B> for $name<T>
+		where
+			B: Send,
+			axum::extract::$name<T>: FromRequest<B>,
+			<axum::extract::$name<T> as FromRequest<B>>::Rejection: StdError,
+		{
+			type Rejection = Error;
+
+			async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+				match axum::extract::$name::<T>::from_request(req).await {
+					Ok(axum::extract::$name(value)) => Ok($name(value)),
+					Err(rejection) => Err(InvalidParam(describe(&rejection))),
+				}
+			}
+		}
+	};
+}
+
+extractor!(
+	/// The json body of a request, also used to answer json.
+	Json
+);
+extractor!(
+	/// Parameters of the path.
+	Path
+);
+extractor!(
+	/// Parameters of the query string.
+	Query
+);
+
+/// The rejection with its causes, axum keeps what exactly is wrong in the sources.
+fn describe(rejection: &dyn StdError) -> String {
+	let mut msgs = vec![rejection.to_string()];
+	let mut source = rejection.source();
+	while let Some(cause) = source {
+		// Composite rejections repeat the message of the one they wrap.
+		let msg = cause.to_string();
+		if msgs.last() != Some(&msg) {
+			msgs.push(msg);
+		}
+		source = cause.source();
+	}
+	msgs.join(": ")
+}
+
+impl<T: Serialize> IntoResponse for Json<T> {
+	fn into_response(self) -> Response {
+		axum::Json(self.0).into_response()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use std::collections::HashMap;
+
+	use axum::{
+		body::{Body, HttpBody},
+		http::{header::CONTENT_TYPE, Request, StatusCode},
+		routing::post,
+		Router,
+	};
+	use serde_json::Value;
+	use tower::ServiceExt;
+
+	use super::{Json, Path};
+
+	#[tokio::test]
+	async fn test_rejection() {
+		async fn handle(Path(id): Path<u64>, Json(body): Json<HashMap<String, u64>>) -> Json<u64> {
+			Json(id + body.len() as u64)
+		}
+		let app = Router::new().route("/:id", post(handle));
+		let request = |id: &str, body: &str| {
+			let request =
+				Request::post(format!("/{}", id)).header(CONTENT_TYPE, "application/json");
+			request.body(Body::from(body.to_string())).unwrap()
+		};
+
+		let response = app.clone().oneshot(request("1", r#"{"a": 1}"#)).await.unwrap();
+		assert_eq!(response.status(), StatusCode::OK);
+
+		for (id, body) in [("one", r#"{"a": 1}"#), ("1", r#"{"a": "one"}"#)] {
+			let response = app.clone().oneshot(request(id, body)).await.unwrap();
+			assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+			let body = response.into_body().data().await.unwrap().unwrap();
+			let body: Value = serde_json::from_slice(&body).unwrap();
+			assert_eq!(body["error_code"], "INVALID_PARAM");
+		}
+	}
+}
@@ -1,31 +1,120 @@
+pub(crate) mod contract_api;
 pub(crate) mod eth_api;
+pub(crate) mod extract;
+pub(crate) mod signer_api;
+
+use std::collections::HashMap;
 
 use axum::{
-	routing::{get, post},
+	async_trait,
+	extract::{FromRequest, Path, RequestParts},
+	http::StatusCode,
+	routing::{delete, get, post},
 	Router,
 };
 
 use serde::{Deserialize, Serialize};
 use utoipa::ToSchema;
 
-use self::eth_api::{
-	call_contract, deploy_contract, eth_accounts, eth_balance, eth_raw_transaction,
-	eth_transaction, query_contract,
+use crate::{
+	error::Error,
+	ethereum::{network, Network},
+	Result,
+};
+
+use self::{
+	contract_api::{
+		compile_contract, delete_contract, get_contract, list_contracts, upload_contract,
+	},
+	eth_api::{
+		broadcast_transaction, call_contract, cancel_transaction, deploy_contract,
+		derive_wallet_accounts, eth_accounts, eth_balance, eth_raw_transaction, eth_transaction,
+		fill_account_nonces, get_deployment, get_nonces, get_transaction, list_deployments,
+		query_contract, sign_message, sign_transaction, speed_up_transaction, verify_message,
+		wallet_accounts,
+	},
+	extract::Json,
+	signer_api::{delete_signer, import_signer, list_signers},
 };
 
 #[derive(Debug, Serialize, Deserialize, ToSchema)]
 pub(crate) struct ResultInfo<T> {
 	code: u16,
 	msg: String,
-	data: T,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	error_code: Option<String>,
+	#[serde(skip_serializing_if = "Option::is_none")]
+	data: Option<T>,
 }
 
 impl<T> ResultInfo<T> {
-	fn new(code: u16, msg: String, data: T) -> ResultInfo<T> {
-		ResultInfo { code, msg, data }
+	pub(crate) fn ok(data: T) -> ResultInfo<T> {
+		let status = StatusCode::OK;
+		ResultInfo {
+			code: status.as_u16(),
+			msg: status.canonical_reason().unwrap().to_string(),
+			error_code: None,
+			data: Some(data),
+		}
+	}
+
+	pub(crate) fn error(status: StatusCode, error_code: &str, msg: String) -> ResultInfo<T> {
+		ResultInfo {
+			code: status.as_u16(),
+			msg,
+			error_code: Some(error_code.to_string()),
+			data: None,
+		}
+	}
+
+	pub(crate) fn with_data(self, data: Option<T>) -> ResultInfo<T> {
+		ResultInfo { data, ..self }
+	}
+}
+
+pub(crate) type ApiResult<T> = Result<Json<ResultInfo<T>>>;
+
+/// Header naming the network of a request, the `/eth/{network}/..` path prefix takes precedence.
+pub(crate) const NETWORK_HEADER: &str = "x-eth-network";
+
+/// The network a request is routed to, taken from the path, the header or the default.
+pub(crate) struct EthNetwork(pub &'static Network);
+
+#[async_trait]
+impl<B: Send> FromRequest<B> for EthNetwork {
+	type Rejection = Error;
+
+	async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+		let params = Path::<HashMap<String, String>>::from_request(req).await;
+		let name = match params.ok().and_then(|Path(mut params)| params.remove("network")) {
+			Some(name) => Some(name),
+			None => req
+				.headers()
+				.get(NETWORK_HEADER)
+				.and_then(|value| value.to_str().ok())
+				.map(|value| value.to_string()),
+		};
+
+		network(name.as_deref()).map(EthNetwork)
 	}
 }
 
+/// First path segments of the eth routes, networks can't be named like them since
+/// `/eth/:network` would be shadowed by the routes of the default network.
+pub const ETH_ROUTE_SEGMENTS: &[&str] = &[
+	"accounts",
+	"balance",
+	"sendTransaction",
+	"sendRawTransaction",
+	"contract",
+	"deployments",
+	"nonces",
+	"tx",
+	"wallet",
+	"message",
+];
+
+/// Served both under `/eth` for the default network and under `/eth/:network`.
 pub fn eth_routes() -> Router {
 	Router::new()
 		.route("/accounts", get(eth_accounts))
@@ -35,4 +124,31 @@ pub fn eth_routes() -> Router {
 		.route("/contract/deploy", post(deploy_contract))
 		.route("/contract/call_fn", post(call_contract))
 		.route("/contract/query_fn", post(query_contract))
+		.route("/deployments", get(list_deployments))
+		.route("/deployments/:id", get(get_deployment))
+		.route("/nonces/:id", get(get_nonces))
+		.route("/nonces/:id/fill", post(fill_account_nonces))
+		.route("/tx/sign", post(sign_transaction))
+		.route("/tx/broadcast", post(broadcast_transaction))
+		.route("/tx/:hash", get(get_transaction))
+		.route("/tx/:hash/speedup", post(speed_up_transaction))
+		.route("/tx/:hash/cancel", post(cancel_transaction))
+		.route("/wallet/accounts", get(wallet_accounts).post(derive_wallet_accounts))
+		.route("/message/sign", post(sign_message))
+		.route("/message/verify", post(verify_message))
+}
+
+/// Contract artifacts, shared by all networks.
+pub fn contract_routes() -> Router {
+	Router::new()
+		.route("/", get(list_contracts).post(upload_contract))
+		.route("/compile", post(compile_contract))
+		.route("/:name", get(get_contract).delete(delete_contract))
+}
+
+/// Keystore signers, shared by all networks.
+pub fn signer_routes() -> Router {
+	Router::new()
+		.route("/", get(list_signers).post(import_signer))
+		.route("/:id", delete(delete_signer))
 }
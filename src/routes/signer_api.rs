@@ -0,0 +1,49 @@
+use crate::ethereum::signer::{self, ImportSignerRequest, SignerInfo};
+
+use super::{
+	extract::{Json, Path},
+	ApiResult, ResultInfo,
+};
+
+#[utoipa::path(
+	get,
+	path = "/signers",
+	responses(
+		(status = 200, description = "List unlocked signers successfully"),
+	),
+)]
+pub(crate) async fn list_signers() -> ApiResult<Vec<SignerInfo>> {
+	Ok(Json(ResultInfo::ok(signer::list())))
+}
+
+#[utoipa::path(
+	post,
+	path = "/signers",
+	request_body = ImportSignerRequest,
+	responses(
+		(status = 200, description = "Import or generate a signer successfully"),
+		(status = 400, description = "Alias or secret key is invalid"),
+		(status = 409, description = "Alias or key is imported already"),
+		(status = 500, description = "No keystore passphrase is configured"),
+	),
+)]
+pub(crate) async fn import_signer(
+	Json(payload): Json<ImportSignerRequest>,
+) -> ApiResult<SignerInfo> {
+	Ok(Json(ResultInfo::ok(signer::import(payload).await?)))
+}
+
+#[utoipa::path(
+	delete,
+	path = "/signers/{id}",
+	responses(
+		(status = 200, description = "Delete signer and move its keystore file to the trash successfully"),
+		(status = 404, description = "Signer not found"),
+	),
+	params(
+		("id" = String, Path, description = "alias or address of the signer"),
+	),
+)]
+pub(crate) async fn delete_signer(Path(id): Path<String>) -> ApiResult<SignerInfo> {
+	Ok(Json(ResultInfo::ok(signer::remove(&id)?)))
+}